@@ -1,14 +1,16 @@
-use std::   fmt::Debug;
-use std::str::FromStr;
+use std::convert::TryFrom                ;
+use std::    fmt::Debug                  ;
+use std::    str::FromStr                ;
+use std::   time::{ Duration, SystemTime };
 
-use ::enigo::{ Key, MouseButton, Enigo, KeyboardControllable, MouseControllable };
+use ::enigo::{ Key, MouseButton };
 
-// TODO: make one global enigo variable instead of instantiating in every method.
+use super::*;
 
 
 // Generic types for both keyboard and mouse buttons.
 //
-#[ derive( Debug, Copy, Clone ) ]
+#[ derive( Debug, Copy, Clone, PartialEq ) ]
 pub enum Clickable
 {
 	Keyboard( Key         ),
@@ -18,31 +20,55 @@ pub enum Clickable
 
 impl Clickable
 {
-	fn press( &self )
+	fn press( &self, out: &mut dyn Output )
 	{
-		let mut enigo = Enigo::new();
+		out.press( *self );
+	}
+
 
-		if      let Clickable::Keyboard( key ) = self { enigo.key_down  ( *key ) }
-		else if let Clickable::Mouse   ( key ) = self { enigo.mouse_down( *key ) }
+	fn release( &self, out: &mut dyn Output )
+	{
+		out.release( *self );
 	}
+}
+
 
 
-	fn release( &self )
+// Several keys that are pressed together, like Control+Shift+F5. They are pressed in order and released
+// in reverse order, so modifiers are down before the key and stay down until after it.
+//
+#[ derive( Debug, Clone, PartialEq ) ]
+//
+pub struct Chord( pub Vec< Clickable > );
+
+
+impl Chord
+{
+	fn press( &self, out: &mut dyn Output )
 	{
-		let mut enigo = Enigo::new();
+		for key in self.0.iter()       { key.press  ( out ); }
+	}
 
-		if      let Clickable::Keyboard( key ) = self { enigo.key_up  ( *key ) }
-		else if let Clickable::Mouse   ( key ) = self { enigo.mouse_up( *key ) }
+
+	fn release( &self, out: &mut dyn Output )
+	{
+		for key in self.0.iter().rev() { key.release( out ); }
 	}
 }
 
 
 
-// Represents an action that can be attached to a gamepad input event
+// Represents an action that can be attached to a gamepad input event.
+// Actions never talk to the OS directly, they send their keyboard and mouse events to the output they are given.
 //
 pub trait Action : Debug
 {
-	fn run( &mut self, state: f32 );
+	fn run( &mut self, state: f32, time: SystemTime, out: &mut dyn Output );
+
+	/// Stop whatever the action is doing and forget about it's state, eg. when the gamepad disconnects
+	/// or padawan shuts down. Keys that are still held down afterwards are released by the gamepad.
+	//
+	fn release( &mut self, _out: &mut dyn Output ) {}
 }
 
 
@@ -50,65 +76,302 @@ pub trait Action : Debug
 
 // Different Actions we can attach to gamepad input events
 //
-#[ derive( Debug, Clone ) ] pub struct PressKey     { pub key   : Clickable                                          }
-#[ derive( Debug, Clone ) ] pub struct ReleaseKey   { pub key   : Clickable                                          }
+#[ derive( Debug, Clone ) ] pub struct PressKey     { pub key   : Chord                                              }
+#[ derive( Debug, Clone ) ] pub struct ReleaseKey   { pub key   : Chord                                              }
 #[ derive( Debug, Clone ) ] pub struct Axis2Button  { pub left  : Clickable, pub right: Clickable, pub deadzone: f32 }
-#[ derive( Debug, Clone ) ] pub struct ToggleButton { pub button: Clickable, pub state: bool                         }
+#[ derive( Debug, Clone ) ] pub struct ToggleButton { pub button: Chord    , pub state: bool                         }
+#[ derive( Debug, Clone ) ] pub struct TapKey       { pub key   : Chord                                              }
+#[ derive( Debug, Clone ) ] pub struct HoldKey      { pub key   : Chord                                              }
+
+#[ derive( Debug, Clone ) ] pub struct MouseMapX    { pub mouse : MouseCfg , pub rest : f32         , pub last: Option< SystemTime > }
+#[ derive( Debug, Clone ) ] pub struct MouseMapY    { pub mouse : MouseCfg , pub rest : f32         , pub last: Option< SystemTime > }
+#[ derive( Debug, Clone ) ] pub struct StickMouse   { pub mouse : MouseCfg , pub rest : ( f32, f32 ), pub last: Option< SystemTime >, pub edge: Option< SystemTime > }
+
+#[ derive( Debug, Clone ) ] pub struct ScrollX      { pub clicks: f32      , pub rest : f32         , pub last: Option< SystemTime > }
+#[ derive( Debug, Clone ) ] pub struct ScrollY      { pub clicks: f32      , pub rest : f32         , pub last: Option< SystemTime > }
+#[ derive( Debug, Clone ) ] pub struct ScrollStep   { pub x     : i32      , pub y    : i32                          }
+
+
+// Repeatedly clicks a key while the input is held. Every interval, the key is held down for
+// duty * interval and released for the rest.
+//
+#[ derive( Debug, Clone ) ]
+//
+pub struct Turbo
+{
+	pub key     : Chord                ,
+	pub interval: Duration             ,
+	pub duty    : f32                  ,
+	pub start   : Option< SystemTime > ,
+	pub down    : bool                 ,
+}
 
-#[ derive( Debug, Clone ) ] pub struct MouseMapX    { pub pixels: f32                                                }
-#[ derive( Debug, Clone ) ] pub struct MouseMapY    { pub pixels: f32                                                }
+
+// Presses keys for the direction a stick is pushed in. The keys are up, down, left and right.
+//
+#[ derive( Debug, Clone ) ]
+//
+pub struct StickKeys
+{
+	pub keys     : [ Chord; 4 ]    ,
+	pub sectors  : Sectors         ,
+	pub threshold: f32             ,
+	pub sector   : Option< usize > ,
+}
 
 
 impl Action for PressKey
 {
-	fn run( &mut self, _state: f32 ) { self.key.press(); }
+	fn run( &mut self, _state: f32, _time: SystemTime, out: &mut dyn Output ) { self.key.press( out ); }
 }
 
 
 impl Action for ReleaseKey
 {
-	fn run( &mut self, _state: f32 ) { self.key.release(); }
+	fn run( &mut self, _state: f32, _time: SystemTime, out: &mut dyn Output ) { self.key.release( out ); }
+}
+
+
+impl Action for TapKey
+{
+	fn run( &mut self, _state: f32, _time: SystemTime, out: &mut dyn Output ) { self.key.press( out ); self.key.release( out ); }
+}
+
+
+// Presses the key while the input is down and releases it when it goes up, for triggers that run on both.
+//
+impl Action for HoldKey
+{
+	fn run( &mut self, state: f32, _time: SystemTime, out: &mut dyn Output )
+	{
+		if state >= 0.5 { self.key.press( out ); }
+		else            { self.key.release( out ); }
+	}
+}
+
+
+
+// The mouse only moves by whole pixels, so what's left is kept for the next tick. Otherwise a stick that is only
+// tilted a little wouldn't move the mouse at all. Within the deadzone of the mouse settings the actions drop what's
+// left, or the drift of a stick at rest would add up to pixels.
+//
+// `speed` is in pixels for one poll interval of the event loop. The actual time since the last tick is used,
+// so the speed doesn't depend on how regularly we get polled. A long pause, eg. when the machine was
+// suspended, counts as a few ticks at most so the pointer doesn't jump across the screen.
+//
+fn mouse_pixels( speed: f32, time: SystemTime, last: &mut Option< SystemTime >, rest: &mut f32 ) -> i32
+{
+	carry( speed * ticks( time, last ), rest )
+}
+
+
+
+// How many poll intervals passed since the last run.
+//
+fn ticks( time: SystemTime, last: &mut Option< SystemTime > ) -> f32
+{
+	let tick    = Duration::from_millis( Gamepad::SLEEP_MS );
+
+	let elapsed = match last.replace( time )
+	{
+		Some( last ) => time.duration_since( last ).unwrap_or( Duration::from_secs( 0 ) ).min( tick * 10 ),
+		None         => tick,
+	};
+
+	( elapsed.as_secs() as f32 * 1000.0 + elapsed.subsec_micros() as f32 / 1000.0 ) / Gamepad::SLEEP_MS as f32
+}
+
+
+
+// Returns the whole part of amount plus what was left over from last time, and keeps the new remainder.
+//
+fn carry( amount: f32, rest: &mut f32 ) -> i32
+{
+	let amount = amount + *rest;
+	let whole  = amount.trunc();
+
+	*rest = amount - whole;
+
+	whole as i32
 }
 
 
 
 impl Action for MouseMapX
 {
-	fn run( &mut self, state: f32 )
+	fn run( &mut self, state: f32, time: SystemTime, out: &mut dyn Output )
 	{
-		let mut enigo = Enigo::new();
+		if self.mouse.idle( state ) { return self.release( out ) }
+
+		let x = mouse_pixels( self.mouse.speed( state ), time, &mut self.last, &mut self.rest );
 
-		enigo.mouse_move_relative( (state * state.abs() * self.pixels) as i32, 0 );
+		out.move_mouse( x, 0 );
 	}
+
+
+	fn release( &mut self, _out: &mut dyn Output ) { self.rest = 0.0; self.last = None; }
 }
 
 
 
 impl Action for MouseMapY
 {
-	fn run( &mut self, state: f32 )
+	fn run( &mut self, state: f32, time: SystemTime, out: &mut dyn Output )
 	{
-		let mut enigo = Enigo::new();
+		if self.mouse.idle( state ) { return self.release( out ) }
+
+		let y = mouse_pixels( self.mouse.speed( state ), time, &mut self.last, &mut self.rest );
 
-		// The mouse move logic starts with 0 at top left, but moving the thumb stick up is posistive, hence the - to invert things.invert
+		// The mouse move logic starts with 0 at top left, but moving the thumb stick up is positive, hence the - to invert things.
 		//
-		enigo.mouse_move_relative( 0, -( state * state.abs() * self.pixels ) as i32 );
+		out.move_mouse( 0, -y );
 	}
+
+
+	fn release( &mut self, _out: &mut dyn Output ) { self.rest = 0.0; self.last = None; }
+}
+
+
+
+// The curve is applied to the distance from the center, the direction stays the same.
+//
+impl StickAction for StickMouse
+{
+	fn run( &mut self, x: f32, y: f32, time: SystemTime, out: &mut dyn Output )
+	{
+		let length = x.hypot( y );
+
+		if self.mouse.idle( length ) { return self.release( out ) }
+
+
+		let ticks = ticks( time, &mut self.last );
+
+
+		let speed = self.mouse.accelerated( length, time, &mut self.edge ) * ticks / length;
+		let dx    = carry( x * speed, &mut self.rest.0 );
+		let dy    = carry( y * speed, &mut self.rest.1 );
+
+		// Up on the stick is positive, up on the screen is negative.
+		//
+		out.move_mouse( dx, -dy );
+	}
+
+
+	fn release( &mut self, _out: &mut dyn Output ) { self.rest = ( 0.0, 0.0 ); self.last = None; self.edge = None; }
+}
+
+
+
+impl StickKeys
+{
+	// The keys to hold for each sector, as bits: 1 up, 2 down, 4 left, 8 right.
+	//
+	const DIRECTIONS: [ u8; 8 ] = [ 8, 9, 1, 5, 4, 6, 2, 10 ];
+
+
+	fn directions( sector: Option< usize > ) -> u8
+	{
+		sector.map_or( 0, |s| Self::DIRECTIONS[ s ] )
+	}
+}
+
+
+
+// Only the keys that differ between the old and the new sector are released and pressed, so going from up
+// to up-right keeps up held.
+//
+impl StickAction for StickKeys
+{
+	fn run( &mut self, x: f32, y: f32, _time: SystemTime, out: &mut dyn Output )
+	{
+		let sector = if x.hypot( y ) < self.threshold { None }
+
+			else { Some( self.sectors.find( x, y, self.sector ) ) }
+		;
+
+		if sector == self.sector { return }
+
+
+		let old = Self::directions( self.sector );
+		let new = Self::directions( sector      );
+
+		for ( i, key ) in self.keys.iter().enumerate()
+		{
+			if old & !new & ( 1 << i ) != 0 { key.release( out ) }
+		}
+
+		for ( i, key ) in self.keys.iter().enumerate()
+		{
+			if new & !old & ( 1 << i ) != 0 { key.press( out ) }
+		}
+
+		self.sector = sector;
+	}
+
+
+	fn release( &mut self, _out: &mut dyn Output ) { self.sector = None; }
+}
+
+
+
+// The wheel only turns by whole clicks, so what's left is kept for the next run. This way a stick that is
+// only tilted a little still scrolls, slowly. Like the mouse, the speed doesn't depend on how often we run.
+//
+fn scroll_clicks( state: f32, clicks: f32, time: SystemTime, last: &mut Option< SystemTime >, rest: &mut f32 ) -> i32
+{
+	carry( state * state.abs() * clicks * ticks( time, last ), rest )
+}
+
+
+
+impl Action for ScrollX
+{
+	fn run( &mut self, state: f32, time: SystemTime, out: &mut dyn Output )
+	{
+		let x = scroll_clicks( state, self.clicks, time, &mut self.last, &mut self.rest );
+
+		out.scroll( x, 0 );
+	}
+
+
+	fn release( &mut self, _out: &mut dyn Output ) { self.rest = 0.0; self.last = None; }
+}
+
+
+
+impl Action for ScrollY
+{
+	fn run( &mut self, state: f32, time: SystemTime, out: &mut dyn Output )
+	{
+		let y = scroll_clicks( state, self.clicks, time, &mut self.last, &mut self.rest );
+
+		out.scroll( 0, y );
+	}
+
+
+	fn release( &mut self, _out: &mut dyn Output ) { self.rest = 0.0; self.last = None; }
+}
+
+
+
+impl Action for ScrollStep
+{
+	fn run( &mut self, _state: f32, _time: SystemTime, out: &mut dyn Output ) { out.scroll( self.x, self.y ); }
 }
 
 
 
 impl Action for Axis2Button
 {
-	fn run( &mut self, state: f32 )
+	fn run( &mut self, state: f32, _time: SystemTime, out: &mut dyn Output )
 	{
-		     if state >  self.deadzone { self.left .release(); self.right.press(); }
-		else if state < -self.deadzone { self.right.release(); self.left .press(); }
+		     if state >  self.deadzone { self.left .release( out ); self.right.press( out ); }
+		else if state < -self.deadzone { self.right.release( out ); self.left .press( out ); }
 
 		else
 		{
-			self.right.release();
-			self.left .release();
+			self.right.release( out );
+			self.left .release( out );
 		}
 	}
 }
@@ -120,22 +383,181 @@ impl Action for Axis2Button
 //
 impl Action for ToggleButton
 {
-	fn run( &mut self, _: f32 )
+	fn run( &mut self, _: f32, _: SystemTime, out: &mut dyn Output )
 	{
-		if   self.state { self.button.release(); }
-		else            { self.button.press  (); }
+		if   self.state { self.button.release( out ); }
+		else            { self.button.press  ( out ); }
 
 		self.state = !self.state;
 	}
+
+
+	fn release( &mut self, out: &mut dyn Output )
+	{
+		if self.state { self.button.release( out ); }
+
+		self.state = false;
+	}
+}
+
+
+
+// Runs on every tick, so it only has to look at the state of the input to know whether it is held.
+//
+impl Action for Turbo
+{
+	fn run( &mut self, state: f32, time: SystemTime, out: &mut dyn Output )
+	{
+		if state < 0.5
+		{
+			self.release( out );
+			return;
+		}
+
+
+		let start    = *self.start.get_or_insert( time );
+		let interval = millis( self.interval ).max( 1 );
+		let phase    = millis( time.duration_since( start ).unwrap_or( Duration::from_secs( 0 ) ) ) % interval;
+		let down     = ( phase as f32 ) < self.duty * interval as f32;
+
+
+		if down != self.down
+		{
+			if down { self.key.press  ( out ); }
+			else    { self.key.release( out ); }
+
+			self.down = down;
+		}
+	}
+
+
+	fn release( &mut self, out: &mut dyn Output )
+	{
+		if self.down { self.key.release( out ); }
+
+		self.down  = false;
+		self.start = None ;
+	}
 }
 
 
 
-impl std::convert::From< &String > for Clickable
+// Plays a list of steps when the input goes down. It starts on the press, then runs on every tick and only does
+// the steps that are due, so waiting doesn't block other inputs. Pressing the input again while the sequence
+// runs does nothing.
+//
+#[ derive( Debug, Clone ) ]
+//
+pub struct Sequence
+{
+	pub steps           : Vec< Step >      ,
+	pub abort_on_release: bool             ,
+	pub next            : Option< usize >  ,
+	pub until           : SystemTime       ,
+	pub was_down        : bool             ,
+	pub held            : Vec< Chord >     ,
+}
+
+
+#[ derive( Debug, Clone ) ]
+//
+pub enum Step
+{
+	Press  ( Chord    ),
+	Release( Chord    ),
+	Tap    ( Chord    ),
+	Wait   ( Duration ),
+	Move   ( i32, i32 ),
+}
+
+
+
+impl Sequence
 {
-	fn from( key: &String ) -> Self
+	pub fn new( steps: Vec< Step >, abort_on_release: bool ) -> Self
 	{
-		match key.as_ref()
+		Self
+		{
+			steps           : steps            ,
+			abort_on_release: abort_on_release ,
+			next            : None             ,
+			until           : SystemTime::now(),
+			was_down        : false            ,
+			held            : Vec::new()       ,
+		}
+	}
+}
+
+
+
+impl Action for Sequence
+{
+	fn run( &mut self, state: f32, time: SystemTime, out: &mut dyn Output )
+	{
+		let down = state >= 0.5;
+
+		if down && !self.was_down && self.next.is_none()
+		{
+			self.next  = Some( 0 );
+			self.until = time     ;
+		}
+
+		if !down && self.was_down && self.abort_on_release
+		{
+			self.release( out );
+		}
+
+		self.was_down = down;
+
+
+		while let Some( i ) = self.next
+		{
+			if time < self.until { return }
+
+
+			match &self.steps[ i ]
+			{
+				Step::Press  ( key  ) => { key.press  ( out ); self.held.push( key.clone() );      }
+				Step::Release( key  ) => { key.release( out ); self.held.retain( |k| k != key );   }
+				Step::Tap    ( key  ) => { key.press  ( out ); key.release( out );                 }
+				Step::Wait   ( wait ) => { self.until = time + *wait;                              }
+				Step::Move   ( x, y ) => { out.move_mouse( *x, *y );                               }
+			}
+
+
+			self.next = if i + 1 < self.steps.len() { Some( i + 1 ) } else { None };
+		}
+	}
+
+
+	// Stops the sequence and releases the keys it pressed.
+	//
+	fn release( &mut self, out: &mut dyn Output )
+	{
+		for key in self.held.drain( .. ) { key.release( out ); }
+
+		self.next = None;
+	}
+}
+
+
+
+/// A duration in whole milliseconds.
+//
+pub(crate) fn millis( d: Duration ) -> u64
+{
+	d.as_secs() * 1000 + d.subsec_millis() as u64
+}
+
+
+
+impl TryFrom< &str > for Clickable
+{
+	type Error = PadError;
+
+	fn try_from( key: &str ) -> Result< Self, PadError >
+	{
+		let key = match key
 		{
 			"mouse_left"   => Clickable::Mouse   ( MouseButton::Left   ),
 			"mouse_middle" => Clickable::Mouse   ( MouseButton::Middle ),
@@ -184,11 +606,273 @@ impl std::convert::From< &String > for Clickable
 			{
 				match char::from_str( key )
 				{
-					Ok ( c ) => Clickable::Keyboard( Key::Layout( c ) ),
-					Err( e ) => panic!( "Keys must be only one char, got: {:?}", e )
+					Ok ( c ) => Clickable::Keyboard( Key::Layout( c ) )                ,
+					Err( _ ) => return Err( PadError::UnknownKey( key.to_string() ) ),
 				}
 
 			}
+		};
+
+		Ok( key )
+	}
+}
+
+
+
+// Keys are separated by a +, eg. "Control+Shift+F5". A + that doesn't follow a key name is the + key itself,
+// so "+" and "Shift++" work.
+//
+impl TryFrom< &str > for Chord
+{
+	type Error = PadError;
+
+	fn try_from( keys: &str ) -> Result< Self, PadError >
+	{
+		let mut names = Vec::new();
+		let mut name  = String::new();
+
+		for c in keys.chars()
+		{
+			if c == '+' && !name.is_empty() { names.push( name ); name = String::new(); }
+			else                            { name.push( c );                          }
 		}
+
+		names.push( name );
+
+
+		// A + at either end, or two in a row, leave an empty key. The + key itself is a name of just "+".
+		//
+		if names.iter().any( |n| n.is_empty() || ( n.starts_with( '+' ) && n.len() > 1 ) )
+		{
+			return Err( PadError::EmptyKey( keys.to_string() ) );
+		}
+
+
+		let keys = names.iter().map( |n| Clickable::try_from( n.as_ref() ) ).collect::< Result< _, _ > >()?;
+
+		Ok( Chord( keys ) )
+	}
+}
+
+
+
+impl std::convert::From< Clickable > for Chord
+{
+	fn from( key: Clickable ) -> Self
+	{
+		Chord( vec![ key ] )
 	}
 }
+
+
+
+impl TryFrom< &StepCfg > for Step
+{
+	type Error = PadError;
+
+	fn try_from( step: &StepCfg ) -> Result< Self, PadError >
+	{
+		let step = match step
+		{
+			StepCfg::Press  ( key  ) => Step::Press  ( Chord::try_from( key.as_ref() )? ),
+			StepCfg::Release( key  ) => Step::Release( Chord::try_from( key.as_ref() )? ),
+			StepCfg::Tap    ( key  ) => Step::Tap    ( Chord::try_from( key.as_ref() )? ),
+			StepCfg::Wait   ( ms   ) => Step::Wait   ( Duration::from_millis( *ms )     ),
+			StepCfg::Move   ( x, y ) => Step::Move   ( *x, *y                           ),
+		};
+
+		Ok( step )
+	}
+}
+
+
+
+#[ cfg( test ) ]
+//
+mod tests
+{
+	use super::*;
+	use std::time::UNIX_EPOCH;
+
+
+	fn down( c: char ) -> OutputEvent { OutputEvent::Press  ( Clickable::Keyboard( Key::Layout( c ) ) ) }
+	fn up  ( c: char ) -> OutputEvent { OutputEvent::Release( Clickable::Keyboard( Key::Layout( c ) ) ) }
+
+
+
+	#[ test ]
+	//
+	fn turbo()
+	{
+		let mut pad = Gamepad::new();
+
+		pad.map_turbo( InputID::South, Chord::try_from( "a" ).unwrap(), Duration::from_millis( 100 ), 0.3 );
+
+
+		let events = vec!
+		[
+			(   0, EventType::ButtonPressed ( InputID::South ) ),
+			(   0, EventType::NoChange                         ),
+			(  20, EventType::NoChange                         ),
+			(  40, EventType::NoChange                         ),
+			(  60, EventType::NoChange                         ),
+			( 110, EventType::NoChange                         ),
+			( 150, EventType::NoChange                         ),
+			( 160, EventType::ButtonReleased( InputID::South ) ),
+			( 170, EventType::NoChange                         ),
+			( 210, EventType::NoChange                         ),
+		];
+
+		let events  = events.into_iter().map( |( ms, event )| PadEvent { pad: 0, event, time: UNIX_EPOCH + Duration::from_millis( ms ) } ).collect();
+		let mut out = RecordOutput::new();
+
+		ScriptSource::new( Vec::new(), events ).play( &mut pad, &mut out );
+
+
+		// Down for the first 30ms of every 100, released for good when the input goes up.
+		//
+		assert_eq!( out.events, vec![ down( 'a' ), up( 'a' ), down( 'a' ), up( 'a' ) ] );
+	}
+
+
+
+	// Released in the middle of the down phase, the key goes up with the input.
+	//
+	#[ test ]
+	//
+	fn turbo_release_while_down()
+	{
+		let mut pad = Gamepad::new();
+
+		pad.map_turbo( InputID::South, Chord::try_from( "a" ).unwrap(), Duration::from_millis( 100 ), 0.5 );
+
+
+		let events = vec!
+		[
+			(  0, EventType::ButtonPressed ( InputID::South ) ),
+			(  5, EventType::NoChange                         ),
+			( 20, EventType::ButtonReleased( InputID::South ) ),
+			( 25, EventType::NoChange                         ),
+			( 30, EventType::NoChange                         ),
+		];
+
+		let events  = events.into_iter().map( |( ms, event )| PadEvent { pad: 0, event, time: UNIX_EPOCH + Duration::from_millis( ms ) } ).collect();
+		let mut out = RecordOutput::new();
+
+		ScriptSource::new( Vec::new(), events ).play( &mut pad, &mut out );
+
+		assert_eq!( out.events, vec![ down( 'a' ), up( 'a' ) ] );
+	}
+
+
+
+	fn key( name: &str ) -> Clickable
+	{
+		Clickable::try_from( name ).unwrap()
+	}
+
+
+	// The message of the error, PadError can't be compared.
+	//
+	fn chord_error( keys: &str ) -> String
+	{
+		Chord::try_from( keys ).unwrap_err().to_string()
+	}
+
+
+
+	// Modifiers go down first and come up last.
+	//
+	#[ test ]
+	//
+	fn chord()
+	{
+		let     chord = Chord::try_from( "Control+Shift+F5" ).unwrap();
+		let mut out = RecordOutput::new();
+
+		chord.press  ( &mut out );
+		chord.release( &mut out );
+
+		assert_eq!( chord.0, vec![ key( "Control" ), key( "Shift" ), key( "F5" ) ] );
+
+		assert_eq!( out.events, vec!
+		[
+			OutputEvent::Press  ( key( "Control" ) ),
+			OutputEvent::Press  ( key( "Shift"   ) ),
+			OutputEvent::Press  ( key( "F5"      ) ),
+			OutputEvent::Release( key( "F5"      ) ),
+			OutputEvent::Release( key( "Shift"   ) ),
+			OutputEvent::Release( key( "Control" ) ),
+		]);
+	}
+
+
+
+	#[ test ]
+	//
+	fn chord_plus_key()
+	{
+		assert_eq!( Chord::try_from( "Shift++" ).unwrap().0, vec![ key( "Shift" ), key( "+" ) ] );
+		assert_eq!( Chord::try_from( "+"       ).unwrap().0, vec![                  key( "+" ) ] );
+	}
+
+
+
+	#[ test ]
+	//
+	fn chord_invalid()
+	{
+		let empty = |keys: &str| PadError::EmptyKey( keys.to_string() ).to_string();
+
+		assert_eq!( chord_error( "a+"         ), empty( "a+"         ) );
+		assert_eq!( chord_error( "+a"         ), empty( "+a"         ) );
+		assert_eq!( chord_error( "Control++a" ), empty( "Control++a" ) );
+
+		assert_eq!( chord_error( "Control+Foo" ), PadError::UnknownKey( "Foo".to_string() ).to_string() );
+	}
+
+
+
+	// A stick at rest that doesn't quite read 0 doesn't move the pointer, however long it stays there.
+	//
+	#[ test ]
+	//
+	fn mouse_drift()
+	{
+		let mut pad = Gamepad::new();
+
+		pad.map_mouse_x( InputID::RightStickX, serde_yaml::from_str( "10.0" ).unwrap() );
+
+		let mut events = vec![ ( 0, EventType::AxisChanged( InputID::RightStickX, 0.04 ) ) ];
+
+		events.extend( ( 1..200 ).map( |i| ( i * 5, EventType::NoChange ) ) );
+
+		let events  = events.into_iter().map( |( ms, event )| PadEvent { pad: 0, event, time: UNIX_EPOCH + Duration::from_millis( ms ) } ).collect();
+		let mut out = RecordOutput::new();
+
+		ScriptSource::new( Vec::new(), events ).play( &mut pad, &mut out );
+
+		assert_eq!( out.events, vec![] );
+	}
+
+
+
+	// The curve is applied to the distance from the center. Curving each axis on it's own would give 9, 16 here,
+	// a steeper angle than the stick.
+	//
+	#[ test ]
+	//
+	fn stick_mouse_diagonal()
+	{
+		let mut mouse = StickMouse { mouse: serde_yaml::from_str( "100.0" ).unwrap(), rest: ( 0.0, 0.0 ), last: None, edge: None };
+		let mut out   = RecordOutput::new();
+
+		for ms in &[ 0, 5, 10 ]
+		{
+			mouse.run( 0.3, 0.4, UNIX_EPOCH + Duration::from_millis( *ms ), &mut out );
+		}
+
+		assert_eq!( out.events, vec![ OutputEvent::MouseMove( 15, -20 ); 3 ] );
+	}
+}
+
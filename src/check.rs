@@ -0,0 +1,767 @@
+// Validation of a configuration file. Loading a config stops at the first problem and serde_yaml loses track of
+// where things are once the profiles are flattened, so here we parse the yaml ourselves, keep the position of
+// every node and check the parts one by one. This way we can report everything that is wrong in one go.
+//
+
+
+use std:: collections::HashMap;
+use std::         fmt          ;
+
+use super::*;
+
+use serde_yaml::{ Value, Mapping };
+use yaml_rust::parser::{ Parser, Event, MarkedEventReceiver };
+use yaml_rust::scanner::{ Marker, TScalarStyle };
+
+
+
+/// Something wrong in a configuration file, and where it is.
+///
+#[ derive( Debug, Clone, PartialEq ) ]
+//
+pub struct Problem
+{
+	pub line  : usize ,
+	pub column: usize ,
+	pub msg   : String,
+}
+
+
+
+/// Check a configuration for everything we can find wrong with it without a gamepad: yaml syntax, unknown inputs,
+/// actions and key names, deadzones, profiles referenced from the `pads` section that don't exist, duplicate keys
+/// and inputs with bindings that contradict each other. The problems are sorted by position in the file.
+///
+pub fn check_config( yaml: &str ) -> Vec< Problem >
+{
+	let mut problems = Vec::new();
+
+
+	let root = match parse( yaml )
+	{
+		Ok( Some( root ) ) => root,
+
+		Ok( None ) =>
+		{
+			problems.push( Problem { line: 1, column: 1, msg: "The configuration is empty".to_string() } );
+			return problems;
+		}
+
+		Err( ( mark, msg ) ) =>
+		{
+			problems.push( Problem::at( mark, msg ) );
+			return problems;
+		}
+	};
+
+
+	duplicates( &root, &mut problems );
+
+
+	let entries = match &root.kind
+	{
+		Kind::Map( entries ) => entries,
+
+		_ =>
+		{
+			problems.push( Problem::at( root.mark, "The configuration must be a map of profiles" ) );
+			return problems;
+		}
+	};
+
+
+	let profiles: Vec< &str > = entries.iter()
+
+		.filter_map( |( k, _ )| k.scalar() )
+		.filter    ( |k| ![ "pads", "output", "layout", "combo_ms" ].contains( k ) )
+		.collect()
+	;
+
+
+	// The uinput output can only type the characters that are on the keyboard layout, so the profiles depend on these.
+	//
+	let setting = |name| entries.iter()
+
+		.find( |( k, _ )| k.scalar() == Some( name ) )
+		.map ( |( _, v )| v.value() )
+	;
+
+	let backend = setting( "output" ).and_then( |v| serde_yaml::from_value::< Backend >( v ).ok() );
+	let layout  = setting( "layout" ).and_then( |v| serde_yaml::from_value::< Layout  >( v ).ok() ).unwrap_or_default();
+	let typing  = if backend == Some( Backend::Uinput ) { Some( layout ) } else { None };
+
+
+	for ( key, val ) in entries
+	{
+		match key.scalar()
+		{
+			Some( "pads"   ) => check_pads( val, &profiles, &mut problems ),
+
+			Some( "output" ) =>
+
+				if let Err( e ) = serde_yaml::from_value::< Backend >( val.value() )
+				{
+					problems.push( Problem::at( val.mark, e ) );
+				},
+
+			Some( "layout" ) =>
+
+				if let Err( e ) = serde_yaml::from_value::< Layout >( val.value() )
+				{
+					problems.push( Problem::at( val.mark, e ) );
+				},
+
+			Some( "combo_ms" ) =>
+
+				if let Err( e ) = serde_yaml::from_value::< u64 >( val.value() )
+				{
+					problems.push( Problem::at( val.mark, e ) );
+				},
+
+			Some( _ ) => check_profile( val, &profiles, typing, &mut problems ),
+			None      => problems.push( Problem::at( key.mark, "Profile names must be strings" ) ),
+		}
+	}
+
+
+	problems.sort_by_key( |p| ( p.line, p.column ) );
+
+	problems
+}
+
+
+
+fn check_pads( pads: &Node, profiles: &[ &str ], problems: &mut Vec< Problem > )
+{
+	let items = match &pads.kind
+	{
+		Kind::Seq( items ) => items,
+		_                  => return problems.push( Problem::at( pads.mark, "pads must be a list of [ selector, profile ]" ) ),
+	};
+
+
+	for item in items
+	{
+		match serde_yaml::from_value::< PadCfg >( item.value() )
+		{
+			Err( e ) => problems.push( Problem::at( item.mark, e ) ),
+
+			Ok( PadCfg( _, name ) ) =>
+
+				if !profiles.contains( &name.as_ref() )
+				{
+					problems.push( Problem::at( item.mark, PadError::UnknownProfile( name ) ) );
+				},
+		}
+	}
+}
+
+
+
+// `typing` is the keyboard layout when the uinput output is used, to check that it can type every key.
+//
+fn check_profile( profile: &Node, profiles: &[ &str ], typing: Option< Layout >, problems: &mut Vec< Problem > )
+{
+	let entries = match &profile.kind
+	{
+		Kind::Map( entries ) => entries,
+		_                    => return problems.push( Problem::at( profile.mark, "A profile must be a map of gamepad inputs to lists of actions" ) ),
+	};
+
+
+	// Mapping the actions on a gamepad validates key names and parameters exactly like when padawan runs.
+	//
+	let mut pad = Gamepad::new();
+
+	// Different keys can be the same binding, like `South + East` and `East + South`, and the actions on a whole
+	// stick use it's axes. These are compared once the whole profile is read.
+	//
+	let mut seen : HashMap< Binding, ( Marker, Option< &str > ) > = HashMap::new();
+	let mut axes : Vec< ( InputID, &str, Marker ) >             = Vec::new();
+	let mut stick: Vec< ( StickID, &str, Marker ) >             = Vec::new();
+
+	for ( key, val ) in entries
+	{
+		let binding = match serde_yaml::from_value::< Binding >( key.value() )
+		{
+			Ok ( binding ) => Some( binding ),
+			Err( e       ) => { problems.push( Problem::at( key.mark, e ) ); None }
+		};
+
+
+		if let Some( binding ) = &binding
+		{
+			match seen.get( binding )
+			{
+				// The same key written the same way is reported by `duplicates`.
+				//
+				Some( ( first, text ) ) if *text != key.scalar() =>
+
+					problems.push( Problem::at( key.mark, format!( "{} is already bound on line {}", binding, first.line() ) ) ),
+
+				Some( _ ) => (),
+				None      => { seen.insert( binding.clone(), ( key.mark, key.scalar() ) ); }
+			}
+		}
+
+
+		let items = match &val.kind
+		{
+			Kind::Seq( items ) => items,
+
+			_ =>
+			{
+				problems.push( Problem::at( val.mark, "The actions for an input must be a list, eg. [ Button: a ]" ) );
+				continue;
+			}
+		};
+
+
+		let mut bound = Vec::new();
+
+		for item in items
+		{
+			let action = match serde_yaml::from_value::< ActionCfg >( item.value() )
+			{
+				Ok ( action ) => action,
+				Err( e      ) => { problems.push( Problem::at( item.mark, e ) ); continue }
+			};
+
+
+			for name in action.profiles()
+			{
+				if !profiles.contains( &name.as_ref() )
+				{
+					problems.push( Problem::at( item.mark, PadError::UnknownProfile( name.clone() ) ) );
+				}
+			}
+
+
+			if let Some( binding ) = &binding
+			{
+				let mut one = Profile::new();
+
+				one.insert( binding.clone(), vec![ action.clone() ] );
+
+				if let Err( e ) = pad.configure( &one )
+				{
+					problems.push( Problem::at( item.mark, e ) );
+				}
+
+				else if let Some( layout ) = typing
+				{
+					if let Err( e ) = keys( &action, layout )
+					{
+						problems.push( Problem::at( item.mark, e ) );
+					}
+				}
+
+				match binding
+				{
+					Binding::Input( input ) if is_stick( *input )      => axes .push( ( *input, action.name(), item.mark ) ),
+					Binding::Stick( id    ) if !is_deadzone( &action ) => stick.push( ( *id   , action.name(), item.mark ) ),
+					_                                                  => ()                                                 ,
+				}
+
+				bound.push( ( action, item.mark ) );
+			}
+		}
+
+
+		if let Some( Binding::Input( input ) ) = binding { conflicts( input, &bound, problems ) }
+	}
+
+
+	// The axes of a stick still get the position after it's Deadzone, but the other stick actions would fight over it.
+	//
+	for &( id, name, mark ) in &stick
+	{
+		for &( input, axis, axis_mark ) in axes.iter().filter( |a| StickID::of( a.0 ) == Some( id ) )
+		{
+			let a = ( name, format!( "{:?}", id    ), mark      );
+			let b = ( axis, format!( "{:?}", input ), axis_mark );
+
+			let ( first, second ) = if a.2.index() < b.2.index() { ( a, b ) } else { ( b, a ) };
+
+			problems.push( Problem::at( second.2, format!
+
+			(
+				"{} on {} conflicts with {} on {} on line {}, a stick is used whole or axis by axis, not both",
+				second.0, second.1, first.0, first.1, first.2.line()
+			)));
+		}
+	}
+}
+
+
+
+#[ cfg( target_os = "linux" ) ]
+//
+fn keys( action: &ActionCfg, layout: Layout ) -> Result< (), PadError >
+{
+	check_keys( action, layout )
+}
+
+
+// Without uinput any key name enigo knows can be typed.
+//
+#[ cfg( not( target_os = "linux" ) ) ]
+//
+fn keys( _action: &ActionCfg, _layout: Layout ) -> Result< (), PadError >
+{
+	Ok(())
+}
+
+
+
+fn is_stick( input: InputID ) -> bool
+{
+	match input
+	{
+		InputID::LeftStickX | InputID::LeftStickY | InputID::RightStickX | InputID::RightStickY => true,
+		_                                                                                       => false,
+	}
+}
+
+
+
+// An input is either used as a button or as an axis. The sticks never send button presses, so OnDown and OnUp
+// actions never fire on them. The buttons are only ever 0 or 1, so actions that follow the value of an input
+// need a stick or an analog trigger. Those can't share it with button actions.
+//
+fn conflicts( input: InputID, bound: &[ ( ActionCfg, Marker ) ], problems: &mut Vec< Problem > )
+{
+	let stick   = is_stick( input );
+	let digital = !stick && !is_analog( input );
+
+
+	for ( action, mark ) in bound
+	{
+		if stick && needs_presses( action )
+		{
+			problems.push( Problem::at( *mark, format!( "{} on {:?} never fires, sticks don't send button presses. Use Axis2Button instead.", action.name(), input ) ) );
+		}
+
+		if digital && follows_value( action )
+		{
+			problems.push( Problem::at( *mark, format!( "{} on {:?} needs an axis, a button is only ever 0 or 1. Bind it to a stick axis or to LeftTrigger2 or RightTrigger2.", action.name(), input ) ) );
+		}
+	}
+
+
+	// On a button the problem is already reported for every action that follows the value.
+	//
+	if digital { return }
+
+
+	let first_axis   = bound.iter().find( |( a, _ )|  follows_value( a ) );
+	let first_button = bound.iter().find( |( a, _ )| !follows_value( a ) );
+
+	if let ( Some( axis ), Some( button ) ) = ( first_axis, first_button )
+	{
+		let ( first, second ) = if axis.1.index() < button.1.index() { ( axis, button ) } else { ( button, axis ) };
+
+		problems.push( Problem::at( second.1, format!
+
+		(
+			"{} conflicts with {} on line {}, {:?} can be used as a button or as an axis, not both",
+			second.0.name(), first.0.name(), first.1.line(), input
+		)));
+	}
+}
+
+
+
+// The triggers that tell how far they are pressed, besides being pressed or not.
+//
+fn is_analog( input: InputID ) -> bool
+{
+	match input
+	{
+		InputID::LeftTrigger2 | InputID::RightTrigger2 => true ,
+		_                                              => false,
+	}
+}
+
+
+
+// Actions that only run when the input is pressed or released.
+//
+fn needs_presses( action: &ActionCfg ) -> bool
+{
+	match action
+	{
+		ActionCfg::Button       ( .. ) |
+		ActionCfg::Toggle       ( .. ) |
+		ActionCfg::SwitchProfile( .. ) |
+		ActionCfg::CycleProfiles( .. ) |
+		ActionCfg::Layer        ( .. ) |
+		ActionCfg::TapHold      { .. } |
+		ActionCfg::MultiTap     { .. } |
+		ActionCfg::ScrollStep   ( .. ) => true ,
+
+		_                              => false,
+	}
+}
+
+
+
+// Actions that translate the value of an axis rather than whether the input is down.
+//
+fn follows_value( action: &ActionCfg ) -> bool
+{
+	match action
+	{
+		ActionCfg::MouseX     ( .. ) |
+		ActionCfg::MouseY     ( .. ) |
+		ActionCfg::ScrollX    ( .. ) |
+		ActionCfg::ScrollY    ( .. ) |
+		ActionCfg::Axis2Button( .. ) => true ,
+
+		_                            => false,
+	}
+}
+
+
+
+fn is_deadzone( action: &ActionCfg ) -> bool
+{
+	match action
+	{
+		ActionCfg::Deadzone{ .. } => true ,
+		_                         => false,
+	}
+}
+
+
+
+// Duplicate keys are not an error for serde_yaml, the last one silently wins.
+//
+fn duplicates( node: &Node, problems: &mut Vec< Problem > )
+{
+	match &node.kind
+	{
+		Kind::Scalar( .. ) => (),
+
+		Kind::Seq( items ) => for item in items { duplicates( item, problems ) },
+
+		Kind::Map( entries ) =>
+		{
+			let mut seen: HashMap< &str, Marker > = HashMap::new();
+
+			for ( key, val ) in entries
+			{
+				if let Some( k ) = key.scalar()
+				{
+					match seen.get( k )
+					{
+						Some( first ) => problems.push( Problem::at( key.mark, format!( "Duplicate key {}, it's already defined on line {}", k, first.line() ) ) ),
+						None          => { seen.insert( k, key.mark ); }
+					}
+				}
+
+				duplicates( val, problems );
+			}
+		}
+	}
+}
+
+
+
+impl Problem
+{
+	fn at( mark: Marker, msg: impl fmt::Display ) -> Self
+	{
+		// yaml-rust counts columns from 0, but lines from 1.
+		//
+		Self { line: mark.line(), column: mark.col() + 1, msg: msg.to_string() }
+	}
+}
+
+
+
+impl fmt::Display for Problem
+{
+	fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result
+	{
+		write!( f, "{}:{}: {}", self.line, self.column, self.msg )
+	}
+}
+
+
+
+
+/// A yaml node that remembers where it starts in the file.
+//
+#[ derive( Debug, Clone ) ]
+//
+struct Node
+{
+	mark: Marker,
+	kind: Kind  ,
+}
+
+
+#[ derive( Debug, Clone ) ]
+//
+enum Kind
+{
+	Scalar( String, TScalarStyle   ),
+	Seq   ( Vec< Node >            ),
+	Map   ( Vec< ( Node, Node ) >  ),
+}
+
+
+
+impl Node
+{
+	fn scalar( &self ) -> Option< &str >
+	{
+		match &self.kind
+		{
+			Kind::Scalar( s, _ ) => Some( s ),
+			_                    => None     ,
+		}
+	}
+
+
+	// Plain scalars are resolved by serde_yaml, so "3.0" becomes a number and "~" null, just like when loading the config.
+	//
+	fn value( &self ) -> Value
+	{
+		match &self.kind
+		{
+			Kind::Scalar( s, TScalarStyle::Plain ) if s.is_empty() => Value::Null,
+
+			Kind::Scalar( s, TScalarStyle::Plain ) => serde_yaml::from_str( s ).unwrap_or_else( |_| Value::String( s.clone() ) ),
+			Kind::Scalar( s, _                   ) => Value::String( s.clone() ),
+
+			Kind::Seq( items   ) => Value::Sequence( items.iter().map( Node::value ).collect() ),
+			Kind::Map( entries ) => Value::Mapping ( entries.iter().map( |( k, v )| ( k.value(), v.value() ) ).collect::< Mapping >() ),
+		}
+	}
+}
+
+
+
+// Builds a tree of nodes from the events of the yaml parser. Only the first document is used.
+//
+#[ derive( Debug, Default ) ]
+//
+struct Builder
+{
+	open   : Vec< ( Marker, usize, bool, Vec< Node > ) >,
+	anchors: HashMap< usize, Node >                    ,
+	root   : Option< Node >                            ,
+}
+
+
+
+fn parse( yaml: &str ) -> Result< Option< Node >, ( Marker, String ) >
+{
+	let mut builder = Builder::default();
+
+	Parser::new( yaml.chars() ).load( &mut builder, false ).map_err( |e|
+	{
+		let mark   = *e.marker();
+		let suffix = format!( " at line {} column {}", mark.line(), mark.col() + 1 );
+
+		( mark, e.to_string().trim_end_matches( &suffix[..] ).to_string() )
+	})?;
+
+	Ok( builder.root )
+}
+
+
+
+impl Builder
+{
+	fn push( &mut self, node: Node, anchor: usize )
+	{
+		if anchor > 0 { self.anchors.insert( anchor, node.clone() ); }
+
+		match self.open.last_mut()
+		{
+			Some( ( _, _, _, items ) ) => items.push( node ),
+			None                       => if self.root.is_none() { self.root = Some( node ) },
+		}
+	}
+}
+
+
+
+impl MarkedEventReceiver for Builder
+{
+	fn on_event( &mut self, ev: Event, mark: Marker )
+	{
+		match ev
+		{
+			Event::Scalar( s, style, anchor, _ ) => self.push( Node { mark, kind: Kind::Scalar( s, style ) }, anchor ),
+
+			Event::SequenceStart( anchor ) => self.open.push( ( mark, anchor, false, Vec::new() ) ),
+			Event::MappingStart ( anchor ) => self.open.push( ( mark, anchor, true , Vec::new() ) ),
+
+			Event::SequenceEnd | Event::MappingEnd =>
+			{
+				if let Some( ( start, anchor, map, items ) ) = self.open.pop()
+				{
+					let kind = if !map { Kind::Seq( items ) } else
+					{
+						let mut items   = items.into_iter();
+						let mut entries = Vec::new();
+
+						while let ( Some( k ), Some( v ) ) = ( items.next(), items.next() ) { entries.push( ( k, v ) ) }
+
+						Kind::Map( entries )
+					};
+
+					self.push( Node { mark: start, kind }, anchor );
+				}
+			}
+
+			// An alias gets the position of where it's used, so problems show up there.
+			//
+			Event::Alias( id ) =>
+			{
+				if let Some( node ) = self.anchors.get( &id ).cloned()
+				{
+					self.push( Node { mark, ..node }, 0 );
+				}
+			}
+
+			_ => (),
+		}
+	}
+}
+
+
+
+
+#[ cfg( test ) ]
+//
+mod tests
+{
+	use super::*;
+
+
+	// The position of every problem and the start of it's message. Yaml doesn't allow tabs for indentation.
+	//
+	fn check( yaml: &str ) -> Vec< ( usize, usize, String ) >
+	{
+		check_config( &yaml.replace( '\t', " " ) ).into_iter().map( |p| ( p.line, p.column, p.msg ) ).collect()
+	}
+
+
+	fn starts( problems: &[ ( usize, usize, String ) ], wanted: &[ ( usize, usize, &str ) ] )
+	{
+		assert_eq!( problems.len(), wanted.len(), "{:?}", problems );
+
+		for ( p, w ) in problems.iter().zip( wanted )
+		{
+			assert!( ( p.0, p.1 ) == ( w.0, w.1 ) && p.2.starts_with( w.2 ), "{:?} is not {:?}", p, w );
+		}
+	}
+
+
+
+	#[ test ]
+	//
+	fn duplicate_key()
+	{
+		starts( &check( "
+base:
+	North: [ Button: a ]
+	South: [ Button: b ]
+	North: [ Button: c ]
+"		), &[ ( 5, 2, "Duplicate key North, it's already defined on line 3" ) ] );
+	}
+
+
+
+	#[ test ]
+	//
+	fn unknown_key()
+	{
+		starts( &check( "
+base:
+	South: [ Button: a   ]
+	North: [ Button: Foo ]
+"		), &[ ( 4, 11, "Unknown key: \"Foo\"" ) ] );
+	}
+
+
+
+	#[ test ]
+	//
+	fn deadzone_out_of_range()
+	{
+		starts( &check( "
+base:
+	LeftStick: [ Deadzone: { inner: 0.2, outer: 1.5 } ]
+"		), &[ ( 3, 15, "The deadzone must be between 0 and 1, got: 1.5" ) ] );
+	}
+
+
+
+	#[ test ]
+	//
+	fn axis_and_button()
+	{
+		starts( &check( "
+base:
+	LeftTrigger2: [ MouseX: 3.0, Button: a ]
+	South       : [ MouseX: 3.0            ]
+"		), &[
+			( 3, 31, "Button conflicts with MouseX on line 3, LeftTrigger2 can be used as a button or as an axis" ),
+			( 4, 18, "MouseX on South needs an axis"                                                             ),
+		]);
+	}
+
+
+
+	#[ test ]
+	//
+	fn unknown_profile()
+	{
+		starts( &check( "
+pads:
+	- [ Id: 0, nope ]
+base:
+	South: [ SwitchProfile: other ]
+"		), &[
+			( 3,  4, "Profile <nope> not found"  ),
+			( 5, 11, "Profile <other> not found" ),
+		]);
+	}
+
+
+
+	// The axes of a stick that has actions of it's own can only get a Deadzone from it.
+	//
+	#[ test ]
+	//
+	fn stick_and_axis()
+	{
+		starts( &check( "
+base:
+	LeftStick  : [ StickKeys: { up: w, down: s, left: a, right: d } ]
+	LeftStickX : [ Axis2Button: [ q, d, 0.5 ] ]
+	RightStick : [ Deadzone: { inner: 0.2 } ]
+	RightStickX: [ MouseX: 3.0 ]
+"		), &[ ( 4, 17, "Axis2Button on LeftStickX conflicts with StickKeys on LeftStick on line 3" ) ] );
+	}
+
+
+
+	#[ test ]
+	//
+	fn same_combo()
+	{
+		starts( &check( "
+base:
+	South + East: [ Button: a ]
+	East+South  : [ Button: b ]
+"		), &[ ( 4, 2, "South + East is already bound on line 3" ) ] );
+	}
+}
+
@@ -1,13 +1,15 @@
 use std:: collections::HashMap;
 use std::     convert::TryFrom;
 use std::        path::PathBuf;
+use std::        time::{ Duration, SystemTime };
+use std::         str::FromStr;
+use std::         fmt         ;
 
 
 use std::fs;
 
-use failure  ::Error  ;
-
 use serde_yaml;
+use serde::{ de, Deserialize, Deserializer, Serialize, Serializer };
 
 use super::*;
 
@@ -15,20 +17,53 @@ use super::*;
 
 /// Internal representation of the yaml config.
 ///
-//  This is a tuple struct to keep the yaml format as concise as possible.
+//  The profiles are flattened into the top level to keep the yaml format as concise as possible.
+//  This means that `pads`, `output`, `layout` and `combo_ms` can not be used as profile names.
 //
 #[ derive( Debug, Deserialize, Serialize, Clone, PartialEq ) ]
 //
 pub struct Config
-(
-	HashMap< String, Profile >
-);
+{
+	#[ serde( default ) ]
+	//
+	pads: Vec< PadCfg >,
+
+	#[ serde( default ) ]
+	//
+	output: Option< Backend >,
+
+	#[ serde( default ) ]
+	//
+	layout: Layout,
+
+	#[ serde( default = "default_combo" ) ]
+	//
+	combo_ms: u64,
+
+	#[ serde( flatten ) ]
+	//
+	profiles: HashMap< String, Profile >,
+}
 
 
 /// A specific profile of mappings from gamepad to mouse-keyboard.
-/// A profile can be switched runtime.
+/// A profile can be switched runtime with the SwitchProfile and CycleProfiles actions.
+//
+pub type Profile = HashMap< Binding, Vec< ActionCfg > >;
+
+
+
+/// What the actions in a profile are bound to. In yaml this is the name of an input, like `South`, several
+/// buttons joined with a +, like `Select + Start`, for a combo, or `LeftStick` or `RightStick` for both axes of a stick.
+//
+#[ derive( Debug, Clone, PartialEq, Eq, Hash ) ]
 //
-pub type Profile = HashMap< InputID, Vec< ActionCfg > >;
+pub enum Binding
+{
+	Input( InputID        ),
+	Combo( Vec< InputID > ),
+	Stick( StickID        ),
+}
 
 
 #[ derive( Debug, Deserialize, Serialize, Clone, PartialEq ) ]
@@ -37,9 +72,367 @@ pub enum ActionCfg
 {
 	Button     ( String              ),
 	Toggle     ( String              ),
-	MouseX     (    f32              ),
-	MouseY     (    f32              ),
+	MouseX     ( MouseCfg            ),
+	MouseY     ( MouseCfg            ),
+	ScrollX    (    f32              ),
+	ScrollY    (    f32              ),
+	ScrollStep (    i32, i32         ),
 	Axis2Button( String, String, f32 ),
+
+	Turbo
+	{
+		key        : String,
+		interval_ms: u64   ,
+
+		#[ serde( default = "default_duty" ) ]
+		//
+		duty       : f32   ,
+	},
+
+	Sequence
+	{
+		steps           : Vec< StepCfg >,
+
+		#[ serde( default ) ]
+		//
+		abort_on_release: bool          ,
+	},
+
+	/// Replace the profile of the gamepad when the input is released.
+	//
+	SwitchProfile( String ),
+
+	/// Switch to the profile that comes after the current one in the list, or the first one if the current
+	/// profile isn't in the list. Every profile in the list needs the same binding to be able to cycle on.
+	//
+	CycleProfiles( Vec< String > ),
+
+	/// Use another profile as an overlay while the input is held. The inputs it binds take precedence, all others
+	/// keep working like in the base profile.
+	//
+	Layer( String ),
+
+	/// Tap one key on a short press, hold another one when the input is held longer than threshold_ms.
+	//
+	TapHold
+	{
+		tap         : String,
+		hold        : String,
+
+		#[ serde( default = "default_threshold" ) ]
+		//
+		threshold_ms: u64   ,
+	},
+
+	/// Tap the first key on a single tap, the second on a double tap, and so on. Taps must follow each other
+	/// within window_ms. A press held longer than hold_ms is not a tap, it ends the series.
+	//
+	MultiTap
+	{
+		taps     : Vec< String >,
+
+		#[ serde( default = "default_window" ) ]
+		//
+		window_ms: u64          ,
+
+		#[ serde( default = "default_threshold" ) ]
+		//
+		hold_ms  : u64          ,
+	},
+
+	/// A circular deadzone for a stick, bound to LeftStick or RightStick. The actions on the axes of the stick
+	/// see the position after the deadzone.
+	//
+	Deadzone
+	{
+		inner: f32,
+
+		#[ serde( default = "default_outer" ) ]
+		//
+		outer: f32,
+	},
+
+	/// Move the mouse with a stick bound to LeftStick or RightStick. Takes the same settings as MouseX.
+	//
+	StickMouse( MouseCfg ),
+
+	/// Press keys for the direction a stick bound to LeftStick or RightStick is pushed in, like WASD. With 8 directions
+	/// the diagonals press two keys. The stick has to be pushed beyond threshold to press anything. diagonal_deg
+	/// is the width of the diagonals, so it only works with 8 directions, it defaults to 45.
+	//
+	StickKeys
+	{
+		up            : String,
+		down          : String,
+		left          : String,
+		right         : String,
+
+		#[ serde( default = "default_directions" ) ]
+		//
+		directions    : u8    ,
+
+		#[ serde( default ) ]
+		//
+		diagonal_deg  : Option< f32 >,
+
+		#[ serde( default = "default_hysteresis" ) ]
+		//
+		hysteresis_deg: f32   ,
+
+		#[ serde( default = "default_press" ) ]
+		//
+		threshold     : f32   ,
+	},
+}
+
+
+fn default_duty     () -> f32 { 0.5 }
+fn default_combo    () -> u64 { Gamepad::COMBO_MS }
+fn default_threshold() -> u64 { 200 }
+fn default_window   () -> u64 { 250 }
+fn default_outer     () -> f32 { 1.0  }
+fn default_directions() -> u8  { 8    }
+fn default_hysteresis() -> f32 { 5.0  }
+fn default_press     () -> f32 { 0.5  }
+
+
+
+impl ActionCfg
+{
+	/// The names of the profiles this action can switch to, or use as a layer.
+	//
+	pub fn profiles( &self ) -> &[ String ]
+	{
+		match self
+		{
+			ActionCfg::SwitchProfile( name  ) => std::slice::from_ref( name ),
+			ActionCfg::Layer        ( name  ) => std::slice::from_ref( name ),
+			ActionCfg::CycleProfiles( names ) => names                      ,
+			_                                 => &[]                        ,
+		}
+	}
+
+
+
+	/// All the key names in the action, like `Control+a`.
+	//
+	pub fn keys( &self ) -> Vec< &str >
+	{
+		match self
+		{
+			ActionCfg::Button     ( key              ) => vec![ key ]                                ,
+			ActionCfg::Toggle     ( key              ) => vec![ key ]                                ,
+			ActionCfg::Axis2Button( left, right, _   ) => vec![ left, right ]                        ,
+			ActionCfg::Turbo      { key, ..          } => vec![ key ]                                ,
+			ActionCfg::TapHold    { tap, hold, ..    } => vec![ tap, hold ]                          ,
+			ActionCfg::MultiTap   { taps, ..         } => taps.iter().map( |k| k.as_ref() ).collect(),
+
+			ActionCfg::StickKeys{ up, down, left, right, .. } => vec![ up, down, left, right ],
+
+			ActionCfg::Sequence{ steps, .. } => steps.iter().filter_map( |step| match step
+			{
+				StepCfg::Press( key ) | StepCfg::Release( key ) | StepCfg::Tap( key ) => Some( key.as_ref() ),
+				_                                                                     => None               ,
+
+			}).collect(),
+
+			_ => Vec::new(),
+		}
+	}
+
+
+
+	/// The name of the action like it's written in yaml.
+	//
+	pub fn name( &self ) -> &'static str
+	{
+		match self
+		{
+			ActionCfg::Button     { .. } => "Button"     ,
+			ActionCfg::Toggle     { .. } => "Toggle"     ,
+			ActionCfg::MouseX     { .. } => "MouseX"     ,
+			ActionCfg::MouseY     { .. } => "MouseY"     ,
+			ActionCfg::ScrollX    { .. } => "ScrollX"    ,
+			ActionCfg::ScrollY    { .. } => "ScrollY"    ,
+			ActionCfg::ScrollStep { .. } => "ScrollStep" ,
+			ActionCfg::Axis2Button{ .. } => "Axis2Button",
+			ActionCfg::Turbo      { .. } => "Turbo"      ,
+			ActionCfg::Sequence   { .. } => "Sequence"   ,
+			ActionCfg::Deadzone   { .. } => "Deadzone"   ,
+			ActionCfg::StickMouse { .. } => "StickMouse" ,
+			ActionCfg::StickKeys  { .. } => "StickKeys"  ,
+
+			ActionCfg::SwitchProfile( .. ) => "SwitchProfile",
+			ActionCfg::CycleProfiles( .. ) => "CycleProfiles",
+			ActionCfg::Layer        ( .. ) => "Layer"        ,
+			ActionCfg::TapHold      { .. } => "TapHold"      ,
+			ActionCfg::MultiTap     { .. } => "MultiTap"     ,
+		}
+	}
+}
+
+
+
+/// How a stick moves the mouse. In yaml either just the speed, `MouseX: 4.0`, or a map to tune the response:
+/// `StickMouse: { pixels: 4.0, curve: { Power: 2.5 }, saturation: 0.95, acceleration: { multiplier: 2.0 } }`.
+/// Pixels is the speed at full tilt for every poll of the event loop. From saturation on, the stick counts
+/// as fully pushed. Below deadzone it counts as centered, so a stick that doesn't quite come back to the center
+/// doesn't creep the pointer along. The deadzone is 0.05 unless given. Acceleration is off unless given, it only
+/// works with StickMouse.
+//
+#[ derive( Debug, Serialize, Clone, PartialEq ) ]
+//
+pub struct MouseCfg
+{
+	pub pixels      : f32                   ,
+
+	#[ serde( default ) ]
+	//
+	pub curve       : Curve                 ,
+
+	#[ serde( default = "default_saturation" ) ]
+	//
+	pub saturation  : f32                   ,
+
+	#[ serde( default = "default_mouse_deadzone" ) ]
+	//
+	pub deadzone    : f32                   ,
+
+	#[ serde( default ) ]
+	//
+	pub acceleration: Option< Acceleration >,
+}
+
+
+fn default_saturation    () -> f32 { 1.0  }
+fn default_mouse_deadzone() -> f32 { 0.05 }
+
+
+
+impl MouseCfg
+{
+	/// The speed in pixels per poll for a deflection between -1 and 1.
+	//
+	pub fn speed( &self, state: f32 ) -> f32
+	{
+		self.curve.apply( state, self.saturation ) * self.pixels
+	}
+
+
+	/// Whether a deflection is within the deadzone, where the mouse doesn't move.
+	//
+	pub fn idle( &self, state: f32 ) -> bool
+	{
+		state.abs() < self.deadzone
+	}
+
+
+	/// Like speed, with the acceleration applied. `edge` is when the stick reached the edge, see `Acceleration::factor`.
+	//
+	pub fn accelerated( &self, state: f32, time: SystemTime, edge: &mut Option< SystemTime > ) -> f32
+	{
+		let factor = match &self.acceleration
+		{
+			Some( accel ) => accel.factor( state.abs(), time, edge ),
+			None          => 1.0                                     ,
+		};
+
+		self.speed( state ) * factor
+	}
+
+
+	pub fn check( &self ) -> Result< (), PadError >
+	{
+		if !( self.saturation > 0.0 && self.saturation <= 1.0 )
+		{
+			return Err( PadError::BadMouse( format!( "the saturation must be above 0 and at most 1, got: {}", self.saturation ) ) );
+		}
+
+		if !( self.deadzone >= 0.0 && self.deadzone < self.saturation )
+		{
+			return Err( PadError::BadMouse( format!( "the deadzone must be at least 0 and below the saturation, got: {}", self.deadzone ) ) );
+		}
+
+		if let Some( accel ) = &self.acceleration { accel.check()? }
+
+		self.curve.check()
+	}
+}
+
+
+
+/// One step of a Sequence. Wait is in milliseconds. Tap presses and releases a key right away,
+/// put a Wait between a Press and a Release for software that doesn't see instant taps.
+//
+#[ derive( Debug, Deserialize, Serialize, Clone, PartialEq ) ]
+//
+pub enum StepCfg
+{
+	Press  ( String   ),
+	Release( String   ),
+	Tap    ( String   ),
+	Wait   ( u64      ),
+	Move   ( i32, i32 ),
+}
+
+
+
+
+/// Assigns a profile to the gamepads matching the selector. In yaml: `[ Name: "Xbox 360 Controller", swtor_basics ]`.
+//
+#[ derive( Debug, Deserialize, Serialize, Clone, PartialEq ) ]
+//
+pub struct PadCfg( pub PadSelect, pub String );
+
+
+/// Ways to recognize a gamepad. `Any` matches every pad, so it can be used as a fallback at the end of the list.
+//
+#[ derive( Debug, Deserialize, Serialize, Clone, PartialEq ) ]
+//
+pub enum PadSelect
+{
+	Id  ( usize  ),
+	Name( String ),
+	Uuid( String ),
+	Any          ,
+}
+
+
+
+
+/// The keyboard layout of the OS. The uinput output sends keys rather than characters, so it needs to know which
+/// key gives a character. Enigo types characters directly and doesn't use this.
+//
+#[ derive( Debug, Deserialize, Serialize, Clone, Copy, PartialEq ) ]
+//
+pub enum Layout
+{
+	/// US qwerty
+	//
+	Qwerty,
+
+	/// French azerty
+	//
+	Azerty,
+}
+
+
+impl Default for Layout
+{
+	fn default() -> Self { Layout::Qwerty }
+}
+
+
+
+
+/// The library used to inject keyboard and mouse events in the OS.
+//
+#[ derive( Debug, Deserialize, Serialize, Clone, Copy, PartialEq ) ]
+//
+pub enum Backend
+{
+	Enigo ,
+	Uinput,
 }
 
 
@@ -49,7 +442,215 @@ impl Config
 {
 	pub fn profiles( &self ) -> &HashMap< String, Profile >
 	{
-		&self.0
+		&self.profiles
+	}
+
+
+	pub fn profile( &self, name: &str ) -> Option< &Profile >
+	{
+		self.profiles.get( name )
+	}
+
+
+	pub fn pads( &self ) -> &Vec< PadCfg >
+	{
+		&self.pads
+	}
+
+
+	pub fn pads_mut( &mut self ) -> &mut Vec< PadCfg >
+	{
+		&mut self.pads
+	}
+
+
+	pub fn output( &self ) -> Option< Backend >
+	{
+		self.output
+	}
+
+
+	pub fn layout( &self ) -> Layout
+	{
+		self.layout
+	}
+
+
+	pub fn combo_window( &self ) -> Duration
+	{
+		Duration::from_millis( self.combo_ms )
+	}
+
+
+	/// The name of the profile to use for a gamepad. The first entry in `pads` that matches wins.
+	//
+	pub fn profile_for( &self, info: &PadInfo ) -> Option< &str >
+	{
+		self.pads.iter().find( |p| p.0.matches( info ) ).map( |p| p.1.as_ref() )
+	}
+}
+
+
+
+impl FromStr for Binding
+{
+	type Err = PadError;
+
+	fn from_str( s: &str ) -> Result< Self, PadError >
+	{
+		if let Ok( stick ) = s.trim().parse() { return Ok( Binding::Stick( stick ) ) }
+
+
+		let mut inputs = Vec::new();
+
+		for name in s.split( '+' )
+		{
+			let input = name.trim().parse()?;
+
+			if !inputs.contains( &input ) { inputs.push( input ) }
+		}
+
+
+		// The order in which a combo is written doesn't matter, `South + East` is the same binding as `East + South`.
+		//
+		inputs.sort_by_key( |i| *i as u32 );
+
+		if inputs.len() == 1 { Ok( Binding::Input( inputs[ 0 ] ) ) }
+		else                 { Ok( Binding::Combo( inputs      ) ) }
+	}
+}
+
+
+
+impl fmt::Display for Binding
+{
+	fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result
+	{
+		match self
+		{
+			Binding::Input( input  ) => write!( f, "{:?}", input ),
+			Binding::Stick( stick  ) => write!( f, "{:?}", stick ),
+
+			Binding::Combo( inputs ) =>
+			{
+				let names: Vec< String > = inputs.iter().map( |i| format!( "{:?}", i ) ).collect();
+
+				write!( f, "{}", names.join( " + " ) )
+			}
+		}
+	}
+}
+
+
+
+impl<'de> Deserialize<'de> for Binding
+{
+	fn deserialize< D: Deserializer<'de> >( deserializer: D ) -> Result< Self, D::Error >
+	{
+		String::deserialize( deserializer )?.parse().map_err( de::Error::custom )
+	}
+}
+
+
+
+// MouseCfg can be written as just a number, or as a map with the same fields as the struct.
+//
+impl<'de> Deserialize<'de> for MouseCfg
+{
+	fn deserialize< D: Deserializer<'de> >( deserializer: D ) -> Result< Self, D::Error >
+	{
+		deserializer.deserialize_any( MouseVisitor )
+	}
+}
+
+
+#[ derive( Deserialize ) ]
+//
+#[ serde( deny_unknown_fields ) ]
+//
+struct MouseFields
+{
+	pixels      : f32                   ,
+
+	#[ serde( default ) ]
+	//
+	curve       : Curve                 ,
+
+	#[ serde( default = "default_saturation" ) ]
+	//
+	saturation  : f32                   ,
+
+	#[ serde( default = "default_mouse_deadzone" ) ]
+	//
+	deadzone    : f32                   ,
+
+	#[ serde( default ) ]
+	//
+	acceleration: Option< Acceleration >,
+}
+
+
+struct MouseVisitor;
+
+
+impl<'de> de::Visitor<'de> for MouseVisitor
+{
+	type Value = MouseCfg;
+
+	fn expecting( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result
+	{
+		write!( f, "a speed in pixels or a map with pixels, curve, saturation, deadzone and acceleration" )
+	}
+
+
+	fn visit_f64< E: de::Error >( self, pixels: f64 ) -> Result< MouseCfg, E >
+	{
+		Ok( MouseCfg
+		{
+			pixels      : pixels as f32           ,
+			curve       : Curve::default()        ,
+			saturation  : default_saturation()    ,
+			deadzone    : default_mouse_deadzone(),
+			acceleration: None                    ,
+		})
+	}
+
+
+	fn visit_i64< E: de::Error >( self, pixels: i64 ) -> Result< MouseCfg, E > { self.visit_f64( pixels as f64 ) }
+	fn visit_u64< E: de::Error >( self, pixels: u64 ) -> Result< MouseCfg, E > { self.visit_f64( pixels as f64 ) }
+
+
+	fn visit_map< A: de::MapAccess<'de> >( self, map: A ) -> Result< MouseCfg, A::Error >
+	{
+		let MouseFields { pixels, curve, saturation, deadzone, acceleration } = MouseFields::deserialize( de::value::MapAccessDeserializer::new( map ) )?;
+
+		Ok( MouseCfg { pixels, curve, saturation, deadzone, acceleration } )
+	}
+}
+
+
+
+impl Serialize for Binding
+{
+	fn serialize< S: Serializer >( &self, serializer: S ) -> Result< S::Ok, S::Error >
+	{
+		serializer.collect_str( self )
+	}
+}
+
+
+
+impl PadSelect
+{
+	pub fn matches( &self, info: &PadInfo ) -> bool
+	{
+		match self
+		{
+			PadSelect::Id  ( id   ) => *id   == info.id                      ,
+			PadSelect::Name( name ) => *name == info.name                    ,
+			PadSelect::Uuid( uuid ) => uuid.eq_ignore_ascii_case( &info.uuid ),
+			PadSelect::Any          => true                                  ,
+		}
 	}
 }
 
@@ -57,14 +658,63 @@ impl Config
 
 impl TryFrom< PathBuf > for Config
 {
-	type Error = Error;
+	type Error = PadError;
 
-	fn try_from( path: PathBuf ) -> Result< Self, Error >
+	fn try_from( path: PathBuf ) -> Result< Self, PadError >
 	{
-		let s: Self = serde_yaml::from_str( &fs::read_to_string( path )? )?;
+		let yaml = fs::read_to_string( &path ).map_err( |e| PadError::io( &path, e ) )?;
+
+		yaml.parse()
+	}
+}
 
-		Ok( s )
+
+
+impl FromStr for Config
+{
+	type Err = PadError;
+
+	// Inside the flattened profiles serde_yaml doesn't know where it is, it gives no position or a wrong one.
+	// check_config parses the yaml itself and keeps the positions, so it's first problem is reported instead.
+	//
+	fn from_str( yaml: &str ) -> Result< Self, PadError >
+	{
+		serde_yaml::from_str( yaml ).map_err( |e| match check_config( yaml ).into_iter().next()
+		{
+			Some( p ) => PadError::Yaml { line: p.line, column: p.column, msg: p.msg },
+			None      => e.into()                                                    ,
+		})
 	}
 }
 
 
+
+
+#[ cfg( test ) ]
+//
+mod tests
+{
+	use super::*;
+
+
+	// The unknown action is on the third line, serde_yaml alone would point at the first one.
+	//
+	#[ test ]
+	//
+	fn error_in_profile()
+	{
+		let yaml = "base:\n  South: [ Button: a ]\n  North: [ Foo: b ]\n";
+
+		match yaml.parse::< Config >()
+		{
+			Err( PadError::Yaml { line, column, msg } ) =>
+			{
+				assert_eq!( ( line, column ), ( 3, 12 ) );
+				assert!( msg.contains( "unknown variant `Foo`" ) );
+			}
+
+			other => panic!( "{:?}", other ),
+		}
+	}
+}
+
@@ -0,0 +1,299 @@
+// Response curves shape how far a stick is pushed into how fast the mouse moves. A curve takes a deflection
+// between 0 and 1 and returns a speed between 0 and 1. The sign of the input is handled outside of the curve.
+// On top of that, acceleration makes the mouse faster the longer the stick is held against the edge.
+//
+
+
+use std::time::{ Duration, SystemTime };
+
+use super::*;
+
+
+
+/// The shape of the response. In yaml: `Linear`, `{ Power: 2.5 }`, `{ Exponential: 3.0 }`, `{ SCurve: 2.0 }`
+/// or `{ Table: [ [ 0.3, 0.1 ], [ 0.8, 0.4 ] ] }`.
+//
+#[ derive( Debug, Deserialize, Serialize, Clone, PartialEq ) ]
+//
+pub enum Curve
+{
+	/// The speed is proportional to the deflection.
+	//
+	Linear,
+
+	/// Deflection to the power of the exponent. Above 1 gives more precision near the center.
+	//
+	Power( f32 ),
+
+	/// Starts slow and grows exponentially. The higher the factor the steeper the end.
+	//
+	Exponential( f32 ),
+
+	/// Slow near the center and near the edge, steep in the middle. 1 is linear, higher is steeper.
+	//
+	SCurve( f32 ),
+
+	/// Points of deflection and speed, with straight lines in between. The curve starts in 0, 0 and
+	/// stays at the speed of the last point beyond it.
+	//
+	Table( Vec< ( f32, f32 ) > ),
+}
+
+
+
+/// The quadratic curve padawan has always used.
+//
+impl Default for Curve
+{
+	fn default() -> Self { Curve::Power( 2.0 ) }
+}
+
+
+
+impl Curve
+{
+	/// Apply the curve to a deflection between -1 and 1. The deflection is first scaled so that `saturation`
+	/// already gives full speed, for sticks that never quite reach the edge.
+	//
+	pub fn apply( &self, x: f32, saturation: f32 ) -> f32
+	{
+		let m = ( x.abs() / saturation ).min( 1.0 );
+
+		let speed = match self
+		{
+			Curve::Linear               => m                                                          ,
+			Curve::Power      ( exp   ) => m.powf( *exp )                                             ,
+			Curve::SCurve     ( k     ) => m.powf( *k ) / ( m.powf( *k ) + ( 1.0 - m ).powf( *k ) ),
+			Curve::Table      ( table ) => Self::interpolate( table, m )                              ,
+
+			Curve::Exponential( k     ) =>
+
+				if k.abs() < 1e-3 { m }
+				else              { ( ( k * m ).exp() - 1.0 ) / ( k.exp() - 1.0 ) }
+		};
+
+		if x < 0.0 { -speed } else { speed }
+	}
+
+
+
+	fn interpolate( table: &[ ( f32, f32 ) ], m: f32 ) -> f32
+	{
+		let mut prev = ( 0.0, 0.0 );
+
+		for &( x, y ) in table
+		{
+			if m <= x { return prev.1 + ( y - prev.1 ) * ( m - prev.0 ) / ( x - prev.0 ) }
+
+			prev = ( x, y );
+		}
+
+		prev.1
+	}
+
+
+
+	/// Make sure the parameters give a curve that goes from 0 to 1.
+	//
+	pub fn check( &self ) -> Result< (), PadError >
+	{
+		let bad = |msg: &str| Err( PadError::BadMouse( msg.to_string() ) );
+
+		match self
+		{
+			Curve::Power ( exp ) if !( *exp > 0.0 ) => bad( "the exponent of Power must be positive"   ),
+			Curve::SCurve( k   ) if !( *k   > 0.0 ) => bad( "the steepness of SCurve must be positive" ),
+
+			Curve::Exponential( k ) if !k.is_finite() => bad( "the factor of Exponential must be a number" ),
+
+			Curve::Table( table ) =>
+			{
+				let mut last = 0.0;
+
+				for &( x, y ) in table
+				{
+					if !( x > last && x <= 1.0 ) { return bad( "the deflections in a Table must go up, from above 0 to 1" ) }
+					if !( y >= 0.0 && y <= 1.0 ) { return bad( "the speeds in a Table must be between 0 and 1"            ) }
+
+					last = x;
+				}
+
+				if table.is_empty() { bad( "a Table needs at least one point" ) } else { Ok(()) }
+			}
+
+			_ => Ok(()),
+		}
+	}
+}
+
+
+
+/// Speeds up the mouse when the stick stays near the edge, for fast turns. In yaml:
+/// `acceleration: { multiplier: 3.0, threshold: 0.9, delay_ms: 200, ramp_ms: 400 }`. Once the stick has been
+/// beyond threshold for delay_ms, the speed grows to multiplier times the normal speed over ramp_ms.
+/// It drops back to normal as soon as the stick goes below threshold.
+//
+#[ derive( Debug, Deserialize, Serialize, Clone, PartialEq ) ]
+//
+#[ serde( deny_unknown_fields ) ]
+//
+pub struct Acceleration
+{
+	pub multiplier: f32,
+
+	#[ serde( default = "default_edge" ) ]
+	//
+	pub threshold : f32,
+
+	#[ serde( default ) ]
+	//
+	pub delay_ms  : u64,
+
+	#[ serde( default = "default_ramp" ) ]
+	//
+	pub ramp_ms   : u64,
+}
+
+
+fn default_edge() -> f32 { 0.9 }
+fn default_ramp() -> u64 { 500 }
+
+
+
+impl Acceleration
+{
+	/// What to multiply the speed with for a deflection between 0 and 1 at `time`. `since` is when the stick
+	/// reached the edge, it is kept up to date here.
+	//
+	pub fn factor( &self, deflection: f32, time: SystemTime, since: &mut Option< SystemTime > ) -> f32
+	{
+		if deflection < self.threshold
+		{
+			*since = None;
+			return 1.0;
+		}
+
+
+		let held  = time.duration_since( *since.get_or_insert( time ) ).unwrap_or( Duration::from_secs( 0 ) );
+		let delay = Duration::from_millis( self.delay_ms );
+
+		if held < delay { return 1.0 }
+
+
+		let ramped   = held - delay;
+		let ramped   = ramped.as_secs() as f32 * 1000.0 + ramped.subsec_millis() as f32;
+		let progress = if self.ramp_ms == 0 { 1.0 } else { ( ramped / self.ramp_ms as f32 ).min( 1.0 ) };
+
+		1.0 + ( self.multiplier - 1.0 ) * progress
+	}
+
+
+
+	pub fn check( &self ) -> Result< (), PadError >
+	{
+		if !( self.multiplier > 0.0 )
+		{
+			return Err( PadError::BadMouse( format!( "the acceleration multiplier must be positive, got: {}", self.multiplier ) ) );
+		}
+
+		if !( self.threshold > 0.0 && self.threshold <= 1.0 )
+		{
+			return Err( PadError::BadMouse( format!( "the acceleration threshold must be above 0 and at most 1, got: {}", self.threshold ) ) );
+		}
+
+		Ok(())
+	}
+}
+
+
+
+
+#[ cfg( test ) ]
+//
+mod tests
+{
+	use super::*;
+
+
+	fn curves() -> Vec< Curve >
+	{
+		vec!
+		[
+			Curve::Linear                                     ,
+			Curve::Power      ( 2.5  )                        ,
+			Curve::Power      ( 0.5  )                        ,
+			Curve::Exponential( 3.0  )                        ,
+			Curve::Exponential( -2.0 )                        ,
+			Curve::Exponential( 0.0  )                        ,
+			Curve::SCurve     ( 2.0  )                        ,
+			Curve::Table( vec![ ( 0.3, 0.1 ), ( 1.0, 1.0 ) ] ),
+		]
+	}
+
+
+	fn close( a: f32, b: f32 ) -> bool
+	{
+		( a - b ).abs() < 1e-6
+	}
+
+
+
+	#[ test ]
+	//
+	fn endpoints()
+	{
+		for curve in curves()
+		{
+			assert!( close( curve.apply(  0.0, 1.0 ),  0.0 ), "{:?}", curve );
+			assert!( close( curve.apply(  1.0, 1.0 ),  1.0 ), "{:?}", curve );
+			assert!( close( curve.apply( -1.0, 1.0 ), -1.0 ), "{:?}", curve );
+		}
+	}
+
+
+
+	#[ test ]
+	//
+	fn monotonic()
+	{
+		for curve in curves()
+		{
+			let speeds: Vec< f32 > = ( 0..=100 ).map( |i| curve.apply( i as f32 / 100.0, 1.0 ) ).collect();
+
+			assert!( speeds.windows( 2 ).all( |w| w[ 0 ] <= w[ 1 ] ), "{:?}", curve );
+		}
+	}
+
+
+
+	// Straight lines from 0, 0 through the points, and the speed of the last point beyond it.
+	//
+	#[ test ]
+	//
+	fn table()
+	{
+		let curve = Curve::Table( vec![ ( 0.2, 0.1 ), ( 0.6, 0.5 ) ] );
+
+		assert!( close( curve.apply(  0.1, 1.0 ),  0.05 ) );
+		assert!( close( curve.apply(  0.2, 1.0 ),  0.1  ) );
+		assert!( close( curve.apply(  0.4, 1.0 ),  0.3  ) );
+		assert!( close( curve.apply(  0.6, 1.0 ),  0.5  ) );
+		assert!( close( curve.apply(  0.9, 1.0 ),  0.5  ) );
+		assert!( close( curve.apply( -0.4, 1.0 ), -0.3  ) );
+	}
+
+
+
+	#[ test ]
+	//
+	fn saturation()
+	{
+		let curve = Curve::Linear;
+
+		assert_eq!( curve.apply(  0.8 , 0.8 ),  1.0 );
+		assert_eq!( curve.apply(  0.95, 0.8 ),  1.0 );
+		assert_eq!( curve.apply( -0.9 , 0.8 ), -1.0 );
+		assert_eq!( curve.apply(  0.4 , 0.8 ),  0.5 );
+	}
+}
+
@@ -0,0 +1,175 @@
+// The errors padawan can run into. Most of them come from a configuration that doesn't make sense,
+// so the messages are written for the person editing config.yml.
+//
+
+
+use std::   io       ;
+use std:: path::Path ;
+
+use failure::Fail;
+
+use super::{ Layout, Backend, InputID };
+
+
+
+/// Everything that can go wrong while loading a configuration and mapping it on a gamepad.
+///
+#[ derive( Debug, Fail ) ]
+//
+pub enum PadError
+{
+	/// A key name in the config that is neither a named key nor a single character.
+	//
+	#[ fail( display = "Unknown key: {:?}. Use a name like F5, Control or mouse_left, or a single character.", _0 ) ]
+	//
+	UnknownKey( String ),
+
+	/// A character that has no key of it's own on the keyboard layout, for the uinput output. That includes
+	/// the characters that need shift.
+	//
+	#[ fail( display = "The uinput output can't type {:?} with the {:?} keyboard layout. Characters that need shift are written as a chord, eg. Shift+a. Set `layout` in the configuration to match your keyboard.", _0, _1 ) ]
+	//
+	CantType( char, Layout ),
+
+	/// A chord like `Control+` with nothing between or after the + signs.
+	//
+	#[ fail( display = "Empty key in {:?}. Keys in a chord are joined with +, for the + key itself write eg. Shift++", _0 ) ]
+	//
+	EmptyKey( String ),
+
+	#[ fail( display = "Unknown gamepad input: {:?}", _0 ) ]
+	//
+	UnknownInput( String ),
+
+	#[ fail( display = "Profile <{}> not found in the configuration file.", _0 ) ]
+	//
+	UnknownProfile( String ),
+
+	#[ fail( display = "No profile given on the command line and no pads section in the configuration file." ) ]
+	//
+	NoProfile,
+
+	#[ fail( display = "The deadzone must be between 0 and 1, got: {}", _0 ) ]
+	//
+	BadDeadzone( f32 ),
+
+	#[ fail( display = "The inner deadzone must be below the outer one, got: {} and {}", _0, _1 ) ]
+	//
+	BadStickDeadzone( f32, f32 ),
+
+	#[ fail( display = "{} only works on a whole stick, bind it to LeftStick or RightStick.", _0 ) ]
+	//
+	NeedsStick( &'static str ),
+
+	#[ fail( display = "{} doesn't work on a whole stick, bind it to one of the axes, like LeftStickX.", _0 ) ]
+	//
+	NotOnStick( &'static str ),
+
+	#[ fail( display = "{:?} can't be part of a combo, only buttons can.", _0 ) ]
+	//
+	NotInCombo( InputID ),
+
+	#[ fail( display = "Invalid StickKeys: {}", _0 ) ]
+	//
+	BadStickKeys( String ),
+
+	#[ fail( display = "Invalid Turbo: {}", _0 ) ]
+	//
+	BadTurbo( String ),
+
+	#[ fail( display = "A Sequence needs at least one step." ) ]
+	//
+	EmptySequence,
+
+	#[ fail( display = "Invalid mouse settings: {}", _0 ) ]
+	//
+	BadMouse( String ),
+
+	#[ fail( display = "Could not read {}: {}", _0, _1 ) ]
+	//
+	Io( String, #[ cause ] io::Error ),
+
+	/// Something wrong with one of the profiles, before any gamepad is connected.
+	//
+	#[ fail( display = "In profile <{}>: {}", _0, _1 ) ]
+	//
+	InProfile( String, Box< PadError > ),
+
+	#[ fail( display = "Found {} problem(s) in {}", _0, _1 ) ]
+	//
+	Problems( usize, String ),
+
+	#[ fail( display = "Could not record to {}: {}", _0, _1 ) ]
+	//
+	Record( String, String ),
+
+	#[ fail( display = "Could not read recording {}: {}", _0, _1 ) ]
+	//
+	Recording( String, String ),
+
+	#[ fail( display = "Could not open the {:?} output: {}", _0, _1 ) ]
+	//
+	Output( Backend, String ),
+
+	#[ fail( display = "Could not initialize gilrs: {}", _0 ) ]
+	//
+	Gilrs( String ),
+
+	#[ fail( display = "Could not install the signal handler: {}", _0 ) ]
+	//
+	Signal( String ),
+
+	/// The configuration isn't valid yaml, or doesn't have the structure we expect.
+	//
+	#[ fail( display = "Invalid configuration at line {}, column {}: {}", line, column, msg ) ]
+	//
+	Yaml { line: usize, column: usize, msg: String },
+
+	/// Like Yaml, for the few problems serde_yaml can't point at, like an empty file.
+	//
+	#[ fail( display = "Invalid configuration: {}", _0 ) ]
+	//
+	Config( String ),
+}
+
+
+
+impl PadError
+{
+	pub fn io( path: &Path, cause: io::Error ) -> Self
+	{
+		PadError::Io( path.display().to_string(), cause )
+	}
+
+
+	/// Tell which profile an error comes from.
+	///
+	pub fn in_profile( name: &str, e: PadError ) -> Self
+	{
+		PadError::InProfile( name.to_string(), Box::new( e ) )
+	}
+}
+
+
+
+impl From< serde_yaml::Error > for PadError
+{
+	fn from( e: serde_yaml::Error ) -> Self
+	{
+		let msg = e.to_string();
+
+		match e.location()
+		{
+			// The message of serde_yaml already ends with the location, we show it in our own format.
+			//
+			Some( loc ) =>
+			{
+				let suffix = format!( " at line {} column {}", loc.line(), loc.column() );
+
+				PadError::Yaml { line: loc.line(), column: loc.column(), msg: msg.trim_end_matches( &suffix[..] ).to_string() }
+			}
+
+			None => PadError::Config( msg ),
+		}
+	}
+}
@@ -3,15 +3,16 @@
 //
 
 
-use std::        time::Duration;
-use std:: collections::HashMap ;
-use std::     convert::From    ;
-use std::       slice::Iter    ;
-use std::      thread          ;
+use std::         cmp::Reverse                  ;
+use std:: collections::HashMap                  ;
+use std::     convert::{ From, TryFrom }        ;
+use std::       slice::Iter                     ;
+use std::         str::FromStr                  ;
+use std::        time::{ Duration, SystemTime };
 
 use super::*;
 
-use gilrs::{ Gilrs, Event, EventType as GEventType, Button as GButton, Axis as GAxis };
+use gilrs::{ EventType as GEventType, Button as GButton, Axis as GAxis };
 
 use self::InputID::*;
 
@@ -26,13 +27,87 @@ pub struct Gamepad
 {
 	// We have an object for each input on the gamepad
 	//
-	inputs: HashMap< InputID, Input >
+	inputs  : HashMap< InputID, Input >        ,
+
+	// The keys our actions hold down, so we can release them when the pad goes away
+	//
+	held    : Held                             ,
+
+	// Inputs that switch profiles, and the switch that was requested but not yet done
+	//
+	switches: HashMap< InputID, ProfileSwitch >,
+	switch  : Option< ProfileSwitch >          ,
+
+	// The layers by the input that activates them, and the one that is active right now
+	//
+	layers  : HashMap< InputID, Layer >        ,
+	layer   : Option< InputID >                ,
+
+	// The combos, largest first, the presses of combo buttons that wait for the rest of a combo, the buttons that are
+	// part of a combo that fired, whose release belongs to the combo, and how long to wait for the rest of a combo
+	//
+	combos   : Vec< Combo >                    ,
+	pending  : Vec< ( InputID, SystemTime ) >  ,
+	swallowed: Vec< InputID >                  ,
+	window   : Duration                        ,
+
+	// The sticks that have something bound to them as a whole, their axis events pass through them first
+	//
+	sticks  : HashMap< StickID, Stick >        ,
+}
+
+
+
+/// An overlay profile that is active while an input is held. It only has the inputs the layer profile binds,
+/// all others fall through to the base profile.
+///
+#[ derive( Debug ) ]
+//
+pub struct Layer
+{
+	name  : String                    ,
+	inputs: HashMap< InputID, Input > ,
+}
+
+
+
+/// Several buttons that do something together when they are pressed at the same time. The combo has an Input
+/// of it's own that is pressed when the last button of the combo goes down, and released when the first one goes up.
+///
+#[ derive( Debug ) ]
+//
+pub struct Combo
+{
+	inputs: Vec< InputID >         ,
+	input : Input                  ,
+	switch: Option< ProfileSwitch >,
+	active: bool                   ,
+}
+
+
+
+/// A request to change the profile of a gamepad. The Gamepad doesn't know about the other profiles,
+/// so it hands this to whoever drives it, see `take_switch`.
+///
+#[ derive( Debug, Clone, PartialEq ) ]
+//
+pub enum ProfileSwitch
+{
+	To   ( String        ),
+	Cycle( Vec< String > ),
 }
 
 
 impl Gamepad
 {
-	const SLEEP_MS: u64 = 5;
+	/// How long the event loop sleeps between polls. Every poll sends a `NoChange` event.
+	///
+	pub const SLEEP_MS: u64 = 5;
+
+	/// How long after the first button of a combo the others can follow by default. The presses of buttons that are part
+	/// of a combo are held back this long, if the combo doesn't complete they are delivered late. See `set_combo_window`.
+	///
+	pub const COMBO_MS: u64 = 50;
 
 
 	pub fn new() -> Self
@@ -46,122 +121,691 @@ impl Gamepad
 		}
 
 
-		Gamepad{ inputs: inputs }
+		Gamepad
+		{
+			inputs  : inputs         ,
+			held    : Held::default(),
+			switches: HashMap::new() ,
+			switch  : None           ,
+			layers  : HashMap::new() ,
+			layer   : None           ,
+			combos   : Vec::new()    ,
+			pending  : Vec::new()    ,
+			swallowed: Vec::new()    ,
+			window   : Duration::from_millis( Self::COMBO_MS ),
+			sticks   : HashMap::new(),
+		}
 	}
 
 
 
-	/// Listens to the event loop of gilrs. This will loop and will not return. Eg. Blocks the current thread.
+	/// How long after the first button of a combo the others can follow.
 	///
-	/// TODO: make a working system for when there is more than 1 gamepad connected.
+	pub fn set_combo_window( &mut self, window: Duration )
+	{
+		self.window = window;
+	}
+
+
+
+	/// Process an event from the gamepad that happened at `time`. All keyboard and mouse events our actions produce are sent to `out`.
 	///
-	pub fn listen( pads: &mut Vec<Gamepad> )
+	pub fn process_event( &mut self, event: EventType, time: SystemTime, out: &mut dyn Output )
 	{
-		let mut gpads   = Gilrs::new().unwrap();
-		let     pad     = pads.first_mut().unwrap();
+		if let EventType::Disconnected = event { return self.release_all( out ) }
 
 
-		loop
+		// Sticks with bindings of their own see the axis events first. The actions on the axes get the position
+		// after the deadzone of the stick, if it has one.
+		//
+		if let EventType::AxisChanged( id, value ) = event
+		{
+			let sticks = &mut self.sticks;
+
+			if let Some( stick ) = StickID::of( id ).and_then( |s| sticks.get_mut( &s ) )
+			{
+				let events = stick.process_axis( id, value, time, &mut Tracker::new( out, &mut self.held ) );
+
+				for event in events { self.dispatch( event, time, out ) }
+
+				return;
+			}
+		}
+
+
+		match event
 		{
-			while let Some( Event { id: _, event, time: _ } ) = gpads.next_event()
+			// Presses of combo buttons wait to see whether the rest of the combo follows.
+			//
+			EventType::ButtonPressed( id ) if self.combos.iter().any( |c| c.inputs.contains( &id ) ) =>
+			{
+				self.pending.push( ( id, time ) );
+
+				return self.start_combos( false, time, out );
+			}
+
+
+			EventType::ButtonReleased( id ) if self.swallowed.contains( &id ) => return self.end_combo( id, time, out ),
+
+			// Released before a larger combo completed. The combos that are complete fire, the other buttons were
+			// just pressed on their own.
+			//
+			EventType::ButtonReleased( id ) if self.pending.iter().any( |p| p.0 == id ) =>
 			{
-				pad.process_event( event.into() );
+				self.start_combos( true, time, out );
+				self.flush_pending( out );
+
+				if self.swallowed.contains( &id ) { return self.end_combo( id, time, out ) }
 			}
 
 
-			pad.process_event( EventType::NoChange );
+			EventType::NoChange =>
+			{
+				let expired = self.pending.first().map_or( false, |p| time.duration_since( p.1 ).unwrap_or( Duration::from_secs( 0 ) ) >= self.window );
+
+				if expired
+				{
+					self.start_combos( true, time, out );
+					self.flush_pending( out );
+				}
+
+
+				let mut tracker = Tracker::new( out, &mut self.held );
+
+				for combo in &mut self.combos { combo.input.process_event( event, time, &mut tracker ) }
+				for stick in self.sticks.values_mut() { stick.tick( time, &mut tracker ) }
+			}
+
+			_ => ()
+		};
+
+
+		self.dispatch( event, time, out );
+	}
+
+
+
+	// Fire the combos that have all their buttons pending, largest first. Those buttons now belong to the combo until
+	// they are released. Unless `finish`, a combo waits as long as a larger one with the same buttons can still complete.
+	//
+	fn start_combos( &mut self, finish: bool, time: SystemTime, out: &mut dyn Output )
+	{
+		loop
+		{
+			let pending: Vec< InputID > = self.pending.iter().map( |p| p.0 ).collect();
+
+			let i = match self.combos.iter().position( |c| c.inputs.iter().all( |i| pending.contains( i ) ) )
+			{
+				Some( i ) => i     ,
+				None      => return,
+			};
+
+
+			let inputs = &self.combos[ i ].inputs;
+
+			let larger = self.combos[ ..i ].iter().any( |c|
+
+				c.inputs.len() > inputs.len() && inputs.iter().all( |i| c.inputs.contains( i ) )
+			);
+
+			if larger && !finish { return }
+
+			self.start_combo( i, time, out );
+		}
+	}
+
+
+
+	fn start_combo( &mut self, i: usize, time: SystemTime, out: &mut dyn Output )
+	{
+		let combo = &mut self.combos[ i ];
+
+		self.pending  .retain( |p| !combo.inputs.contains( &p.0 ) );
+		self.swallowed.extend( combo.inputs.iter().cloned()        );
+
+		combo.active = true;
+
+		let mut tracker = Tracker::new( out, &mut self.held );
+
+		combo.input.process_event( EventType::ButtonPressed( combo.inputs[ 0 ] ), time, &mut tracker );
+	}
+
+
+
+	// The first button of a combo that goes up releases the combo. The release of the others does nothing.
+	//
+	fn end_combo( &mut self, id: InputID, time: SystemTime, out: &mut dyn Output )
+	{
+		self.swallowed.retain( |i| *i != id );
+
+		for combo in self.combos.iter_mut().filter( |c| c.active && c.inputs.contains( &id ) )
+		{
+			combo.active = false;
+
+			let mut tracker = Tracker::new( out, &mut self.held );
 
+			combo.input.process_event( EventType::ButtonReleased( combo.inputs[ 0 ] ), time, &mut tracker );
 
-			thread::sleep( Duration::from_millis ( Self::SLEEP_MS ) );
+			if let Some( switch ) = &combo.switch { self.switch = Some( switch.clone() ) }
 		}
 	}
 
 
 
-	pub fn process_event( &mut self, event: EventType )
+	// Deliver the presses we held back for a combo that didn't happen, with their original time.
+	//
+	fn flush_pending( &mut self, out: &mut dyn Output )
 	{
+		for ( id, time ) in std::mem::replace( &mut self.pending, Vec::new() )
+		{
+			self.dispatch( EventType::ButtonPressed( id ), time, out );
+		}
+	}
+
+
+
+	// Send an event to the input it belongs to, or to the layer that takes it over.
+	//
+	fn dispatch( &mut self, event: EventType, time: SystemTime, out: &mut dyn Output )
+	{
+		if let EventType::ButtonReleased( id ) = event
+		{
+			if let Some( switch ) = self.switches.get( &id ) { self.switch = Some( switch.clone() ) }
+		}
+
+
 		match event
 		{
-			EventType::ButtonPressed ( button    ) => self.input_mut( button.into() ).process_event( event ),
-			EventType::ButtonReleased( button    ) => self.input_mut( button.into() ).process_event( event ),
-			EventType::ButtonChanged ( button, _ ) => self.input_mut( button.into() ).process_event( event ),
-			EventType::ButtonRepeated( button    ) => self.input_mut( button.into() ).process_event( event ),
-			EventType::AxisChanged   ( axis  , _ ) => self.input_mut( axis  .into() ).process_event( event ),
-			EventType::NoChange                    => for (_, i) in &mut self.inputs { i.process_event( event ) },
-			EventType::Connected                   => (),
-			EventType::Disconnected                => (),
-			EventType::Dropped                     => (),
+			EventType::ButtonPressed ( id ) if self.layers.contains_key( &id ) => self.set_layer( Some( id ), out ),
+			EventType::ButtonReleased( id ) if self.layer == Some( id )        => self.set_layer( None    , out ),
+
+			_ => ()
+		};
+
+
+		// Inputs bound by the active layer take precedence over the base profile.
+		//
+		let mut layer = match self.layer
+		{
+			Some( id ) => self.layers.get_mut( &id ),
+			None       => None                      ,
+		};
+
+
+		let mut out = Tracker::new( out, &mut self.held );
+
+		match event
+		{
+			EventType::ButtonPressed ( id    ) |
+			EventType::ButtonReleased( id    ) |
+			EventType::ButtonChanged ( id, _ ) |
+			EventType::ButtonRepeated( id    ) |
+			EventType::AxisChanged   ( id, _ ) =>
+			{
+				let input = match layer.as_mut().and_then( |l| l.inputs.get_mut( &id ) )
+				{
+					Some( input ) => Some( input )            ,
+					None          => self.inputs.get_mut( &id ),
+				};
+
+				if let Some( input ) = input { input.process_event( event, time, &mut out ) }
+			}
+
+
+			EventType::NoChange =>
+			{
+				for input in self.inputs.values_mut()
+				{
+					if layer.as_ref().map_or( false, |l| l.inputs.contains_key( &input.id ) ) { continue }
+
+					input.process_event( event, time, &mut out );
+				}
+
+				if let Some( layer ) = layer
+				{
+					for input in layer.inputs.values_mut() { input.process_event( event, time, &mut out ) }
+				}
+			}
+
+
+			EventType::Connected    => (),
+			EventType::Disconnected => (),
+			EventType::Dropped      => (),
 		};
 	}
 
 
 
-	pub fn input( &self, id: InputID ) -> &Input
+	/// Release every key and mouse button held by our actions and reset the state of all inputs.
+	/// The configuration stays in place, so the pad works again as soon as it reconnects.
+	///
+	pub fn release_all( &mut self, out: &mut dyn Output )
+	{
+		let mut tracker = Tracker::new( out, &mut self.held );
+
+		for input in self.inputs.values_mut() { input.release_all( &mut tracker ) }
+
+		for layer in self.layers.values_mut()
+		{
+			for input in layer.inputs.values_mut() { input.release_all( &mut tracker ) }
+		}
+
+		for combo in &mut self.combos
+		{
+			combo.input.release_all( &mut tracker );
+			combo.active = false;
+		}
+
+		for stick in self.sticks.values_mut() { stick.release_all( &mut tracker ) }
+
+		self.held.release_all( out );
+		self.layer = None;
+
+		self.pending  .clear();
+		self.swallowed.clear();
+	}
+
+
+
+	// The inputs of the old and the new layer change hands between the layer and the base profile. Everything held
+	// on them is released, so no key stays down because the input that should release it now does something else.
+	// An input that is still held after the change has to be pressed again.
+	//
+	fn set_layer( &mut self, layer: Option< InputID >, out: &mut dyn Output )
+	{
+		let affected: Vec< InputID > = self.layer.iter().chain( layer.iter() )
+
+			.filter_map( |id| self.layers.get( id ) )
+			.flat_map  ( |l | l.inputs.keys().cloned() )
+			.collect()
+		;
+
+
+		for id in affected
+		{
+			let mut tracker = Tracker::new( out, &mut self.held );
+
+			if let Some( input ) = self.inputs.get_mut( &id ) { input.release_all( &mut tracker ) }
+
+			for l in self.layers.values_mut()
+			{
+				if let Some( input ) = l.inputs.get_mut( &id ) { input.release_all( &mut tracker ) }
+			}
+
+			self.held.release_input( id, out );
+		}
+
+
+		self.layer = layer;
+	}
+
+
+
+	pub fn input( &self, id: InputID ) -> Option< &Input >
+	{
+		self.inputs.get( &id )
+	}
+
+
+
+	pub fn input_mut( &mut self, id: InputID ) -> Option< &mut Input >
+	{
+		self.inputs.get_mut( &id )
+	}
+
+
+
+	// Every InputID has an Input, they are all created in new.
+	//
+	fn add_trigger( &mut self, id: InputID, trigger: Trigger )
+	{
+		if let Some( input ) = self.inputs.get_mut( &id ) { input.add_trigger( trigger ) }
+	}
+
+
+
+	/// The profile switch requested by the last event, if any. The caller is expected to look up the
+	/// profile and call `reconfigure` with it.
+	///
+	pub fn take_switch( &mut self ) -> Option< ProfileSwitch >
+	{
+		self.switch.take()
+	}
+
+
+
+	/// Replace the current profile, and load it's layers from `config`. The new profile is mapped on a
+	/// separate gamepad first, so if it fails the old one stays as it was. Everything held by the old profile
+	/// is released before the triggers of the new one are installed. The sticks stay where they are, the new
+	/// profile gets their position at `time`.
+	///
+	pub fn reconfigure( &mut self, profile: &Profile, config: &Config, time: SystemTime, out: &mut dyn Output ) -> Result< (), PadError >
+	{
+		let mut fresh = Gamepad::new();
+
+		fresh.configure       ( profile )?;
+		fresh.configure_layers( config  )?;
+
+
+		// Sticks only send events when they move. Without this, a stick that is held on MouseX would stop
+		// moving the mouse until it's moved again. The position before the deadzone is kept by the stick, if any.
+		//
+		let mut axes = Vec::new();
+
+		for &id in &[ StickID::LeftStick, StickID::RightStick ]
+		{
+			let ( x, y ) = id.axes();
+
+			let ( vx, vy ) = match self.sticks.get( &id )
+			{
+				Some( stick ) => stick.raw(),
+				None          => ( self.state( x ), self.state( y ) ),
+			};
+
+			axes.push( ( x, vx ) );
+			axes.push( ( y, vy ) );
+		}
+
+
+		// This resets the state of the inputs, the buttons are only pressed again when the new profile sees them go down.
+		//
+		self.release_all( out );
+
+		for ( id, new ) in fresh.inputs
+		{
+			if let Some( input ) = self.inputs.get_mut( &id )
+			{
+				input.triggers   = new.triggers  ;
+				input.tap_window = new.tap_window;
+				input.tap_hold   = new.tap_hold  ;
+				input.taps.clear();
+			}
+		}
+
+		self.switches = fresh.switches;
+		self.layers   = fresh.layers  ;
+		self.combos   = fresh.combos  ;
+		self.sticks   = fresh.sticks  ;
+		self.switch   = None          ;
+
+
+		for ( id, value ) in axes
+		{
+			if value != 0.0 { self.process_event( EventType::AxisChanged( id, value ), time, out ) }
+		}
+
+		Ok(())
+	}
+
+
+
+	// The value of an input in the base profile.
+	//
+	fn state( &self, id: InputID ) -> f32
 	{
-		self.inputs.get( &id ).unwrap()
+		self.inputs.get( &id ).map_or( 0.0, |input| input.state )
 	}
 
 
 
-	pub fn input_mut( &mut self, id: InputID ) -> &mut Input
+	/// Load the profiles of the layers bound in the current profile. The layer profiles can't switch
+	/// profiles or have layers, combos or bindings on whole sticks of their own, those bindings are ignored.
+	///
+	pub fn configure_layers( &mut self, config: &Config ) -> Result< (), PadError >
 	{
-		self.inputs.get_mut( &id ).unwrap()
+		for layer in self.layers.values_mut()
+		{
+			let profile = config.profile( &layer.name ).ok_or_else( || PadError::UnknownProfile( layer.name.clone() ) )?;
+			let mut pad = Gamepad::new();
+
+			pad.configure( profile )?;
+
+			layer.inputs = pad.inputs.into_iter().filter( |( _, input )| !input.triggers.is_empty() ).collect();
+		}
+
+		Ok(())
 	}
 
 
 
 	/// Map configuration to actual event handlers on our inputs.
+	/// Fails on the first key name or parameter that doesn't make sense. The inputs that were mapped before that keep their triggers.
+	///
+	pub fn configure( &mut self, profile: &Profile ) -> Result< (), PadError >
+	{
+		for (binding, actions) in profile
+		{
+			match binding
+			{
+				Binding::Input( input  ) => self.map_config( *input        , actions )?,
+				Binding::Combo( inputs ) => self.map_combo ( inputs.clone(), actions )?,
+				Binding::Stick( stick  ) => self.map_stick ( *stick        , actions )?,
+			}
+		}
+
+		Ok(())
+	}
+
+
+
+	/// Map actions on a combo of buttons. The combo can switch profiles, but it can't activate a layer.
+	/// The sticks are never pressed, so they can't be part of a combo.
+	///
+	pub fn map_combo( &mut self, inputs: Vec< InputID >, actions: &Vec< ActionCfg > ) -> Result< (), PadError >
+	{
+		if let Some( stick ) = inputs.iter().find( |i| StickID::of( **i ).is_some() )
+		{
+			return Err( PadError::NotInCombo( *stick ) );
+		}
+
+
+		let first = match inputs.first()
+		{
+			Some( first ) => *first        ,
+			None          => return Ok(()),
+		};
+
+
+		// Map on a scratch pad, and take the input of the first button for the combo.
+		//
+		let mut pad = Gamepad::new();
+
+		pad.map_config( first, actions )?;
+
+
+		if let Some( input ) = pad.inputs.remove( &first )
+		{
+			let switch = pad.switches.remove( &first );
+
+			self.combos.push( Combo { inputs, input, switch, active: false } );
+
+			// Sorted so that the largest combo wins when several complete at once, and the same one every time
+			// when they are the same size.
+			//
+			self.combos.sort_by_key( |c| ( Reverse( c.inputs.len() ), c.inputs.iter().map( |i| *i as usize ).collect::< Vec< _ > >() ) );
+		}
+
+		Ok(())
+	}
+
+
+
+	/// Map actions on a stick as a whole. Only the actions that need both axes can be bound like this.
 	///
-	pub fn configure( &mut self, profile: &Profile )
+	pub fn map_stick( &mut self, id: StickID, actions: &Vec< ActionCfg > ) -> Result< (), PadError >
 	{
-		for (input, actions) in profile { self.map_config( input.clone(), actions ) }
+		for action in actions
+		{
+			match action
+			{
+				ActionCfg::Deadzone{ inner, outer } =>
+				{
+					let deadzone = Deadzone::new( *inner, *outer )?;
+
+					self.sticks.entry( id ).or_insert_with( || Stick::new( id ) ).set_deadzone( deadzone );
+				}
+
+
+				ActionCfg::StickMouse( mouse ) => { mouse.check()?; self.map_stick_mouse( id, mouse.clone() ) }
+
+
+				ActionCfg::StickKeys{ up, down, left, right, directions, diagonal_deg, hysteresis_deg, threshold } =>
+				{
+					if !( *threshold > 0.0 && *threshold < 1.0 )
+					{
+						return Err( PadError::BadStickKeys( format!( "threshold must be between 0 and 1, got: {}", threshold ) ) );
+					}
+
+					let keys =
+					[
+						Chord::try_from( up   .as_ref() )?,
+						Chord::try_from( down .as_ref() )?,
+						Chord::try_from( left .as_ref() )?,
+						Chord::try_from( right.as_ref() )?,
+					];
+
+					let sectors = Sectors::new( *directions, *diagonal_deg, *hysteresis_deg )?;
+
+					self.map_stick_keys( id, keys, sectors, *threshold )
+				}
+
+				_ => return Err( PadError::NotOnStick( action.name() ) ),
+			}
+		}
+
+		Ok(())
+	}
+
+
+
+	/// Move the mouse with a stick. Unlike MouseX and MouseY on the axes, the curve is applied to how far the stick
+	/// is pushed, in whatever direction, so diagonals move as fast as straight lines and keep their angle.
+	///
+	pub fn map_stick_mouse( &mut self, id: StickID, mouse: MouseCfg )
+	{
+		let sm    = StickMouse{ mouse, rest: ( 0.0, 0.0 ), last: None, edge: None };
+		let trig  = StickTrigger::OnNoChange( Box::new( sm ) );
+
+		self.sticks.entry( id ).or_insert_with( || Stick::new( id ) ).add_trigger( trig );
+	}
+
+
+
+	/// Press keys for the direction a stick is pushed in, eg. WASD for movement. Unlike Axis2Button on both axes
+	/// the directions are sectors of a circle, so the diagonals are as wide as you want them to be.
+	///
+	pub fn map_stick_keys( &mut self, id: StickID, keys: [ Chord; 4 ], sectors: Sectors, threshold: f32 )
+	{
+		let sk    = StickKeys{ keys, sectors, threshold, sector: None };
+		let trig  = StickTrigger::OnChange( Box::new( sk ) );
+
+		self.sticks.entry( id ).or_insert_with( || Stick::new( id ) ).add_trigger( trig );
 	}
 
 
 
 	/// Map configuration to actual event handlers on our inputs.
-	//  Potential other actions:
-	//  - fire: repeatedly fire clicks (width interval)
-	//  - sequence: send other actions in a sequence (with interval?)
-	//
+	///
 	#[inline]
 	//
-	pub fn map_config( &mut self, input: InputID, actions: &Vec< ActionCfg > )
+	pub fn map_config( &mut self, input: InputID, actions: &Vec< ActionCfg > ) -> Result< (), PadError >
 	{
 		for action in actions
 		{
 			match action
 			{
-				ActionCfg::Button( which  ) => self.map_button ( input, Clickable::from( which ) ),
-				ActionCfg::Toggle( which  ) => self.map_toggle ( input, Clickable::from( which ) ),
-				ActionCfg::MouseX( pixels ) => self.map_mouse_x( input, *pixels                  ),
-				ActionCfg::MouseY( pixels ) => self.map_mouse_y( input, *pixels                  ),
+				ActionCfg::Button( which  ) => self.map_button ( input, Chord::try_from( which.as_ref() )? ),
+				ActionCfg::Toggle( which  ) => self.map_toggle ( input, Chord::try_from( which.as_ref() )? ),
+				// One axis doesn't know how far the whole stick is pushed, it's the edge for the acceleration.
+				//
+				ActionCfg::MouseX( mouse  ) | ActionCfg::MouseY( mouse ) if mouse.acceleration.is_some() =>
+
+					return Err( PadError::BadMouse( format!( "acceleration only works with StickMouse, {} is one axis", action.name() ) ) ),
+
+				ActionCfg::MouseX( mouse  ) => { mouse.check()?; self.map_mouse_x( input, mouse.clone() ) }
+				ActionCfg::MouseY( mouse  ) => { mouse.check()?; self.map_mouse_y( input, mouse.clone() ) }
+
+				ActionCfg::ScrollX   ( clicks ) => self.map_scroll_x   ( input, *clicks ),
+				ActionCfg::ScrollY   ( clicks ) => self.map_scroll_y   ( input, *clicks ),
+				ActionCfg::ScrollStep( x, y   ) => self.map_scroll_step( input, *x, *y  ),
 
 
 				ActionCfg::Axis2Button( left, right, deadzone ) =>
+				{
+					if *deadzone < 0.0 || *deadzone > 1.0 { return Err( PadError::BadDeadzone( *deadzone ) ) }
+
+					let left  = Clickable::try_from( left .as_ref() )?;
+					let right = Clickable::try_from( right.as_ref() )?;
+
+					self.map_axis2button( input, left, right, *deadzone )
+				}
+
+
+				ActionCfg::Turbo{ key, interval_ms, duty } =>
+				{
+					if *interval_ms == 0                 { return Err( PadError::BadTurbo( "interval_ms must be above 0".to_string() ) ) }
+					if !( *duty >= 0.0 && *duty <= 1.0 ) { return Err( PadError::BadTurbo( format!( "duty must be between 0 and 1, got: {}", duty ) ) ) }
+
+					self.map_turbo( input, Chord::try_from( key.as_ref() )?, Duration::from_millis( *interval_ms ), *duty )
+				}
+
+
+				ActionCfg::Sequence{ steps, abort_on_release } =>
+				{
+					if steps.is_empty() { return Err( PadError::EmptySequence ) }
+
+					let steps = steps.iter().map( Step::try_from ).collect::< Result< _, _ > >()?;
+
+					self.map_sequence( input, steps, *abort_on_release )
+				}
+
 
-					self.map_axis2button( input, Clickable::from( left ), Clickable::from( right ), *deadzone ),
+				ActionCfg::SwitchProfile( name  ) => self.map_switch( input, ProfileSwitch::To   ( name .clone() ) ),
+				ActionCfg::CycleProfiles( names ) => self.map_switch( input, ProfileSwitch::Cycle( names.clone() ) ),
+				ActionCfg::Layer        ( name  ) => self.map_layer ( input, name.clone()                        ),
+
+
+				ActionCfg::MultiTap{ taps, window_ms, hold_ms } =>
+				{
+					let keys = taps.iter().map( |k| Chord::try_from( k.as_ref() ) ).collect::< Result< _, _ > >()?;
+
+					self.map_multi_tap( input, keys, Duration::from_millis( *window_ms ), Duration::from_millis( *hold_ms ) )
+				}
+
+
+				ActionCfg::TapHold{ tap, hold, threshold_ms } =>
+				{
+					let tap  = Chord::try_from( tap .as_ref() )?;
+					let hold = Chord::try_from( hold.as_ref() )?;
+
+					self.map_tap_hold( input, tap, hold, Duration::from_millis( *threshold_ms ) )
+				}
+
+
+				ActionCfg::Deadzone  { .. } |
+				ActionCfg::StickKeys { .. } |
+				ActionCfg::StickMouse( .. ) => return Err( PadError::NeedsStick( action.name() ) ),
 			};
 		}
 
+
+		Ok(())
 	}
 
 
 
-	/// Map a button on the gamepad to a button on keyboard or mouse.
+	/// Map a button on the gamepad to a button on keyboard or mouse, or a chord of them like Control+Shift+F5.
 	///
-	pub fn map_button( &mut self, input_id: InputID, key: Clickable )
+	pub fn map_button( &mut self, input_id: InputID, key: Chord )
 	{
-		let act   = PressKey  { key: key };
+		let act   = PressKey  { key: key.clone() };
 		let act2  = ReleaseKey{ key: key };
 
 		let trig  = Trigger::OnDown( Box::new( act  ) );
 		let trig2 = Trigger::OnUp  ( Box::new( act2 ) );
 
-		let input = self.input_mut( input_id );
-
-		input.add_trigger( trig  );
-		input.add_trigger( trig2 );
+		self.add_trigger( input_id, trig  );
+		self.add_trigger( input_id, trig2 );
 	}
 
 
@@ -170,15 +814,12 @@ impl Gamepad
 	/// This will toggle the state of a keyboard or mouse button between up and down on a click on the input button.
 	/// It holds the button down until clicked again.
 	///
-	pub fn map_toggle( &mut self, input_id: InputID, key: Clickable )
+	pub fn map_toggle( &mut self, input_id: InputID, key: Chord )
 	{
 		let mt    = ToggleButton{ button: key, state: false };
 		let tt    = Trigger::OnUp( Box::new( mt ) );
 
-		let input = self.input_mut( input_id );
-
-
-		input.add_trigger( tt );
+		self.add_trigger( input_id, tt );
 	}
 
 
@@ -186,107 +827,379 @@ impl Gamepad
 	/// Map an input to the mouse movement (X-axis). This is most useful with the sticks on the gamepad, but it can be used also
 	/// with other buttons. Using four buttons you can get all directions of the mouse. Use the pixels parameter with a negative value
 	/// to reverse the direction.
-	/// @param pixels defines how fast to move the mouse. You can consider 4.0 a default value. Adapt from there to your needs.
+	/// @param mouse.pixels defines how fast to move the mouse, in pixels per `SLEEP_MS` at full tilt. You can consider 4.0 a default value.
+	/// Adapt from there to your needs. Fractions of a pixel add up over time, so slight tilts still move the mouse slowly.
+	/// The curve and saturation of `mouse` decide how the speed grows with the deflection of the stick.
+	///
+	pub fn map_mouse_x( &mut self, input_id: InputID, mouse: MouseCfg )
+	{
+		let mm    = MouseMapX{ mouse, rest: 0.0, last: None };
+		let trig  = Trigger::OnNoChange( Box::new( mm ) );
+
+		self.add_trigger( input_id, trig );
+	}
+
+
+
+	/// See map_mouse_x. Everything works the same except it's on the Y-axis.
+	///
+	pub fn map_mouse_y( &mut self, input_id: InputID, mouse: MouseCfg )
+	{
+		let mm    = MouseMapY{ mouse, rest: 0.0, last: None };
+		let trig  = Trigger::OnNoChange( Box::new( mm ) );
+
+		self.add_trigger( input_id, trig );
+	}
+
+
+	/// Turn the mouse wheel horizontally, proportional to the input. `clicks` is how many wheel clicks per tick at full tilt,
+	/// so it should be well below 1, eg. 0.05. Fractions of a click add up, so slow tilts scroll slowly rather than not at all.
+	///
+	pub fn map_scroll_x( &mut self, input_id: InputID, clicks: f32 )
+	{
+		let sc    = ScrollX{ clicks: clicks, rest: 0.0, last: None };
+		let trig  = Trigger::OnNoChange( Box::new( sc ) );
+
+		self.add_trigger( input_id, trig );
+	}
+
+
+
+	/// See map_scroll_x. Pushing the stick up scrolls up.
+	///
+	pub fn map_scroll_y( &mut self, input_id: InputID, clicks: f32 )
+	{
+		let sc    = ScrollY{ clicks: clicks, rest: 0.0, last: None };
+		let trig  = Trigger::OnNoChange( Box::new( sc ) );
+
+		self.add_trigger( input_id, trig );
+	}
+
+
+
+	/// Turn the mouse wheel by a number of clicks when the input is pressed. Positive is right and up.
+	///
+	pub fn map_scroll_step( &mut self, input_id: InputID, x: i32, y: i32 )
+	{
+		let act   = ScrollStep{ x: x, y: y };
+		let trig  = Trigger::OnDown( Box::new( act ) );
+
+		self.add_trigger( input_id, trig );
+	}
+
+
+	// This allows to connect a thumb stick to 4 buttons, for example movement with keys adws
+	// We couple 2 buttons to one axis, eg. a and d on the X-axis. A will be held down while
+	// the stick is to the left, D will be held down when the stick is to the right.
+	//
+	//
+	pub fn map_axis2button( &mut self, input_id: InputID, left: Clickable, right: Clickable, deadzone: f32 )
+	{
+		let act   = Axis2Button{ left: left, right: right, deadzone: deadzone };
+		let trig  = Trigger::OnChange( Box::new( act ) );
+
+		self.add_trigger( input_id, trig );
+	}
+
+
+
+	/// Repeatedly click a key on the keyboard or mouse while the input is held. The key is held down
+	/// for duty * interval, then released for the rest of the interval.
+	/// This runs on the NoChange tick, so the timing is only as precise as the poll interval.
+	///
+	pub fn map_turbo( &mut self, input_id: InputID, key: Chord, interval: Duration, duty: f32 )
+	{
+		let act   = Turbo{ key: key, interval: interval, duty: duty, start: None, down: false };
+		let trig  = Trigger::OnNoChange( Box::new( act ) );
+
+		self.add_trigger( input_id, trig );
+	}
+
+
+
+	/// Play a sequence of steps when the input goes down. The sequence doesn't block, other inputs keep working while
+	/// it waits. With abort_on_release, releasing the input stops the sequence and releases the keys it pressed.
+	/// It starts on the press, so a press and release in the same poll still play it. The tick does the waits.
+	///
+	pub fn map_sequence( &mut self, input_id: InputID, steps: Vec< Step >, abort_on_release: bool )
+	{
+		let act   = Sequence::new( steps, abort_on_release );
+		let trig  = Trigger::OnPressAndTick( Box::new( act ) );
+
+		self.add_trigger( input_id, trig );
+	}
+
+
+
+	/// Tap the `tap` key when the input is released within `threshold`, hold the `hold` key once the input has been
+	/// down longer than that until it is released. The tap can only be sent on release, once we know it was short.
+	///
+	pub fn map_tap_hold( &mut self, input_id: InputID, tap: Chord, hold: Chord, threshold: Duration )
+	{
+		let trig  = Trigger::OnTap ( threshold,        Box::new( TapKey { key: tap  } ) );
+		let trig2 = Trigger::OnHold( threshold, false, Box::new( HoldKey{ key: hold } ) );
+
+		self.add_trigger( input_id, trig  );
+		self.add_trigger( input_id, trig2 );
+	}
+
+
+
+	/// Tap the first key on a single tap, the second on a double tap and so on. The taps of a series must follow each
+	/// other within `window`. A series is only known to be over when the window expires after the last tap, so a
+	/// single tap is delayed by the window. Reaching the last key in the list sends it right away. A press held
+	/// longer than `hold` is not a tap, it ends the series without counting.
+	///
+	pub fn map_multi_tap( &mut self, input_id: InputID, keys: Vec< Chord >, window: Duration, hold: Duration )
+	{
+		for ( i, key ) in keys.into_iter().enumerate()
+		{
+			self.add_trigger( input_id, Trigger::OnTaps( i + 1, Box::new( TapKey{ key } ) ) );
+		}
+
+		if let Some( input ) = self.inputs.get_mut( &input_id ) { input.set_tap_window( window, hold ) }
+	}
+
+
+
+	/// Switch profiles when the input is released. Switching on release means the new profile doesn't
+	/// get the release of a press it never saw.
 	///
-	pub fn map_mouse_x( &mut self, input_id: InputID, pixels: f32 )
+	pub fn map_switch( &mut self, input_id: InputID, switch: ProfileSwitch )
+	{
+		self.switches.insert( input_id, switch );
+	}
+
+
+
+	/// Activate the layer profile with the given name while the input is held. Call `configure_layers`
+	/// once the profile is configured to load it.
+	///
+	pub fn map_layer( &mut self, input_id: InputID, name: String )
+	{
+		self.layers.insert( input_id, Layer { name, inputs: HashMap::new() } );
+	}
+}
+
+
+
+
+/// The representation of any input (button) on the gamepad
+///
+#[ derive( Debug ) ]
+//
+pub struct Input
+{
+	id         : InputID        ,
+	triggers   : Vec< Trigger > ,
+	state      : f32            ,
+	old_state  : f32            ,
+	pressed    : bool           ,
+
+	// The time of the press event, for triggers that care about how long the input is held
+	//
+	pressed_at : Option< SystemTime >,
+
+	// The release times of the taps in the current series, how long to wait for the next one and how long
+	// a press can last to still count as a tap
+	//
+	taps       : Vec< SystemTime >   ,
+	tap_window : Option< Duration >  ,
+	tap_hold   : Duration            ,
+}
+
+
+
+impl Input
+{
+	pub fn new( id: InputID ) -> Self
 	{
-		let input = self.input_mut( input_id );
-		let mm    = MouseMapX{ pixels: pixels };
+		Self
+		{
+			id         : id         ,
+			triggers   : Vec::new() ,
+			state      : 0.0        ,
+			old_state  : 0.0        ,
+			pressed    : false      ,
+			pressed_at : None       ,
+			taps       : Vec::new() ,
+			tap_window : None       ,
+			tap_hold   : Duration::from_millis( 0 ),
+		}
+	}
+
+
+
+	pub fn process_event( &mut self, event: EventType, time: SystemTime, out: &mut dyn Output )
+	{
+		out.source( self.id );
+
+
+		// The state is kept up to date even if no trigger listens to the event, because actions on
+		// other triggers, like OnNoChange, need it.
+		//
+		match event
+		{
+			EventType::ButtonPressed ( ..       ) => self.set_state( 1.0   ),
+			EventType::ButtonReleased( ..       ) => self.set_state( 0.0   ),
+			EventType::ButtonChanged ( _, state ) => self.set_state( state ),
+			EventType::AxisChanged   ( _, state ) => self.set_state( state ),
+
+			_ => ()
+		};
+
+
+		// A release of a press we never saw, eg. from before a profile switch, has nothing to release.
+		//
+		match event
+		{
+			EventType::ButtonPressed ( .. ) => { self.pressed = true; self.pressed_at = Some( time ) }
+			EventType::ButtonReleased( .. ) => { if !self.pressed { return } self.pressed = false       }
+
+			_ => ()
+		};
+
+
+		let st   = self.state;
+		let held = self.pressed_at.map( |at| time.duration_since( at ).unwrap_or( Duration::from_secs( 0 ) ) );
+
+		for trigger in &mut self.triggers
+		{
+			match event
+			{
+				EventType::ButtonPressed ( .. )      => match trigger
+				{
+					Trigger::OnDown        ( act ) => act.run( st, time, out ),
+					Trigger::OnPressAndTick( act ) => act.run( st, time, out ),
+					_                              => (),
+				},
+
+				EventType::ButtonReleased( .. )      => match trigger
+				{
+					Trigger::OnUp          ( act             ) => act.run( st, time, out ),
+					Trigger::OnPressAndTick( act             ) => act.run( st, time, out ),
+					Trigger::OnTap         ( max, act        ) => if held.map_or( false, |h| h < *max ) { act.run( st, time, out ) },
+					Trigger::OnHold        ( min, fired, act ) =>
+
+						// Held long enough, but released before a tick could fire it. It still happened, if briefly.
+						//
+						if      *fired                              { *fired = false; act.run( st, time, out ) }
+						else if held.map_or( false, |h| h >= *min ) { act.run( 1.0, time, out ); act.run( st, time, out ) },
+
+					_                                          => (),
+				},
+
+				EventType::ButtonRepeated( .. )      => (),
+
+				EventType::NoChange                  => match trigger
+				{
+					Trigger::OnNoChange    ( act             ) => act.run( st, time, out ),
+					Trigger::OnPressAndTick( act             ) => act.run( st, time, out ),
+
+					Trigger::OnHold        ( min, fired, act ) =>
+
+						if !*fired && self.pressed && held.map_or( false, |h| h >= *min ) { *fired = true; act.run( st, time, out ) },
+
+					_                                          => (),
+				},
+
+				EventType::ButtonChanged( .. )       => if let Trigger::OnChange  ( act ) = trigger { act.run( st, time, out ) },
+				EventType::AxisChanged  ( .. )       => if let Trigger::OnChange  ( act ) = trigger { act.run( st, time, out ) },
+
+				_ => ()
+			};
+
+		}
 
-		let tr    = Trigger::OnChange  ( Box::new( mm.clone() ) );
-		let tr2   = Trigger::OnNoChange( Box::new( mm         ) );
 
-		input.add_trigger( tr  );
-		input.add_trigger( tr2 );
-	}
+		if !self.pressed { self.pressed_at = None }
 
 
+		// A series of taps ends when no new press comes within the window after the last release,
+		// or right away when it reaches the highest count an OnTaps trigger listens for.
+		//
+		if let Some( window ) = self.tap_window
+		{
+			let expired = self.taps.last().map_or( false, |&last| time.duration_since( last ).unwrap_or( Duration::from_secs( 0 ) ) >= window );
 
-	/// See map_mouse_x. Everything works the same except it's on the Y-axis.
-	///
-	pub fn map_mouse_y( &mut self, input_id: InputID, pixels: f32 )
-	{
-		let input = self.input_mut( input_id );
-		let mm    = MouseMapY{ pixels: pixels };
+			match event
+			{
+				// Only a press whose length we know can be a tap, a release without one is for a press we never saw.
+				//
+				EventType::ButtonReleased( .. ) if held.map_or( false, |h| h <= self.tap_hold ) =>
+				{
+					self.taps.push( time );
+
+					if self.taps.len() >= self.max_taps() { self.end_taps( time, out ) }
+				}
 
-		let tr    = Trigger::OnChange  ( Box::new( mm.clone() ) );
-		let tr2   = Trigger::OnNoChange( Box::new( mm         ) );
+				EventType::ButtonReleased( .. ) => self.end_taps( time, out ),
 
-		input.add_trigger( tr  );
-		input.add_trigger( tr2 );
+				EventType::ButtonPressed( .. ) | EventType::NoChange if expired => self.end_taps( time, out ),
+
+				_ => ()
+			}
+		}
 	}
 
 
-	// This allows to connect a thumb stick to 4 buttons, for example movement with keys adws
-	// We couple 2 buttons to one axis, eg. a and d on the X-axis. A will be held down while
-	// the stick is to the left, D will be held down when the stick is to the right.
-	//
+
+	// Run the OnTaps triggers for the number of taps in the series that just ended.
 	//
-	pub fn map_axis2button( &mut self, input_id: InputID, left: Clickable, right: Clickable, deadzone: f32 )
+	fn end_taps( &mut self, time: SystemTime, out: &mut dyn Output )
 	{
-		let input = self.input_mut( input_id );
+		let count = self.taps.len();
 
-		let act   = Axis2Button{ left: left, right: right, deadzone: deadzone };
-		let trig  = Trigger::OnChange( Box::new( act ) );
+		self.taps.clear();
 
-		input.add_trigger( trig );
+		for trigger in &mut self.triggers
+		{
+			if let Trigger::OnTaps( n, act ) = trigger
+			{
+				if *n == count { act.run( 0.0, time, out ) }
+			}
+		}
 	}
-}
 
 
 
-
-/// The representation of any input (button) on the gamepad
-///
-#[ derive( Debug ) ]
-//
-pub struct Input
-{
-	_id        : InputID        ,
-	triggers   : Vec< Trigger > ,
-	state      : f32            ,
-	old_state  : f32            ,
-}
+	fn max_taps( &self ) -> usize
+	{
+		self.triggers.iter().filter_map( |t| match t { Trigger::OnTaps( n, _ ) => Some( *n ), _ => None } ).max().unwrap_or( 0 )
+	}
 
 
 
-impl Input
-{
-	pub fn new( id: InputID ) -> Self
+	/// Wait `window` after a tap for another one, before the OnTaps triggers for the series run. Presses longer
+	/// than `hold` don't count as taps.
+	///
+	pub fn set_tap_window( &mut self, window: Duration, hold: Duration )
 	{
-		Self
-		{
-			_id        : id         ,
-			triggers   : Vec::new() ,
-			state      : 0.0        ,
-			old_state  : 0.0        ,
-		}
+		self.tap_window = Some( window );
+		self.tap_hold   = hold         ;
 	}
 
 
 
-	pub fn process_event( &mut self, event: EventType )
+	/// Release everything held by the actions on this input and go back to the rest state.
+	///
+	pub fn release_all( &mut self, out: &mut dyn Output )
 	{
-		let os = &mut self.old_state;
-		let st = &mut self.state;
+		out.source( self.id );
 
 		for trigger in &mut self.triggers
 		{
-			match event
-			{
-				EventType::ButtonPressed ( .. )      => if let Trigger::OnDown    ( act ) = trigger { *os = *st; *st = 1.0; act.run( *st ) },
-				EventType::ButtonReleased( .. )      => if let Trigger::OnUp      ( act ) = trigger { *os = *st; *st = 0.0; act.run( *st ) },
-				EventType::ButtonRepeated( .. )      => (),
-
-				EventType::NoChange                  => if let Trigger::OnNoChange( act ) = trigger { act.run( *st ) },
+			if let Trigger::OnHold( _, fired, _ ) = trigger { *fired = false }
 
-				EventType::ButtonChanged( _, state ) => if let Trigger::OnChange  ( act ) = trigger { *os = *st; *st = state; act.run( *st ) },
-				EventType::AxisChanged  ( _, state ) => if let Trigger::OnChange  ( act ) = trigger { *os = *st; *st = state; act.run( *st ) },
+			trigger.action_mut().release( out );
+		}
 
-				_ => ()
-			};
+		self.state      = 0.0  ;
+		self.old_state  = 0.0  ;
+		self.pressed    = false;
+		self.pressed_at = None ;
 
-		}
+		self.taps.clear();
 	}
 
 
@@ -369,13 +1282,52 @@ pub enum Trigger
 	OnUp      ( Box< dyn Action > ),
 	OnChange  ( Box< dyn Action > ),
 	OnNoChange( Box< dyn Action > ),
+
+	/// On release, if the input was held for less than the duration.
+	//
+	OnTap( Duration, Box< dyn Action > ),
+
+	/// Once the input has been held for the duration, checked on every tick. The bool tells whether it fired,
+	/// if it did, the action runs again on release.
+	//
+	OnHold( Duration, bool, Box< dyn Action > ),
+
+	/// When a series of exactly this many taps ends, see `Input::set_tap_window`.
+	//
+	OnTaps( usize, Box< dyn Action > ),
+
+	/// On press, on release and on every tick, for actions that start on the press and carry on by themselves.
+	//
+	OnPressAndTick( Box< dyn Action > ),
+}
+
+
+
+impl Trigger
+{
+	pub fn action_mut( &mut self ) -> &mut dyn Action
+	{
+		match self
+		{
+			Trigger::OnDown    ( act ) => act.as_mut(),
+			Trigger::OnUp      ( act ) => act.as_mut(),
+			Trigger::OnChange  ( act ) => act.as_mut(),
+			Trigger::OnNoChange( act ) => act.as_mut(),
+
+			Trigger::OnTap ( _,    act ) => act.as_mut(),
+			Trigger::OnHold( _, _, act ) => act.as_mut(),
+			Trigger::OnTaps( _,    act ) => act.as_mut(),
+
+			Trigger::OnPressAndTick( act ) => act.as_mut(),
+		}
+	}
 }
 
 
 
 /// Gamepad event.
 //
-#[ derive( Debug, Clone, Copy, PartialEq ) ]
+#[ derive( Debug, Clone, Copy, PartialEq, Serialize, Deserialize ) ]
 //
 pub enum EventType
 {
@@ -484,6 +1436,22 @@ impl InputID
 
 
 
+impl FromStr for InputID
+{
+	type Err = PadError;
+
+	fn from_str( name: &str ) -> Result< Self, PadError >
+	{
+		InputID::variants()
+
+			.find( |id| format!( "{:?}", id ) == name )
+			.cloned()
+			.ok_or_else( || PadError::UnknownInput( name.to_string() ) )
+	}
+}
+
+
+
 /// Translate GEventType to EventType
 //
 impl From< GEventType > for EventType
@@ -565,3 +1533,443 @@ impl From< GAxis > for InputID
 		}
 	}
 }
+
+
+
+#[ cfg( test ) ]
+//
+mod tests
+{
+	use super::*;
+	use std::time::UNIX_EPOCH;
+	use enigo::Key;
+
+
+	// Yaml doesn't allow tabs for indentation.
+	//
+	fn config( yaml: &str ) -> Config
+	{
+		serde_yaml::from_str( &yaml.replace( '\t', " " ) ).unwrap()
+	}
+
+
+	fn pad( config: &Config, profile: &str ) -> Gamepad
+	{
+		let mut pad = Gamepad::new();
+
+		pad.configure       ( config.profile( profile ).unwrap() ).unwrap();
+		pad.configure_layers( config                             ).unwrap();
+
+		pad
+	}
+
+
+	// Play events given as milliseconds since the start and return what the pad sent.
+	//
+	fn play( pad: &mut Gamepad, events: &[ ( u64, EventType ) ] ) -> Vec< OutputEvent >
+	{
+		let events = events.iter().map( |&( ms, event )| PadEvent { pad: 0, event, time: at( ms ) } ).collect();
+		let mut out = RecordOutput::new();
+
+		ScriptSource::new( Vec::new(), events ).play( pad, &mut out );
+
+		out.events
+	}
+
+
+	fn at( ms: u64 ) -> SystemTime
+	{
+		UNIX_EPOCH + Duration::from_millis( ms )
+	}
+
+
+	fn down( c: char ) -> OutputEvent { OutputEvent::Press  ( Clickable::Keyboard( Key::Layout( c ) ) ) }
+	fn up  ( c: char ) -> OutputEvent { OutputEvent::Release( Clickable::Keyboard( Key::Layout( c ) ) ) }
+
+
+	use self::EventType::{ ButtonPressed as Pressed, ButtonReleased as Released, NoChange };
+
+
+
+	#[ test ]
+	//
+	fn layer()
+	{
+		let config = config( "
+			base : { South: [ Button: a ], LeftTrigger: [ Layer: shift ] }
+			shift: { South: [ Button: b ] }
+		");
+
+		let mut pad = pad( &config, "base" );
+
+		let events = play( &mut pad,
+		&[
+			(  0, Pressed ( LeftTrigger ) ),
+			( 10, Pressed ( South       ) ),
+			( 20, Released( South       ) ),
+			( 30, Released( LeftTrigger ) ),
+			( 40, Pressed ( South       ) ),
+			( 50, Released( South       ) ),
+		]);
+
+		assert_eq!( events, vec![ down( 'b' ), up( 'b' ), down( 'a' ), up( 'a' ) ] );
+	}
+
+
+
+	// Releasing the layer input while a key of the layer is held releases it, the base profile doesn't know about it.
+	//
+	#[ test ]
+	//
+	fn layer_released_first()
+	{
+		let config = config( "
+			base : { South: [ Button: a ], LeftTrigger: [ Layer: shift ] }
+			shift: { South: [ Button: b ] }
+		");
+
+		let mut pad = pad( &config, "base" );
+
+		let events = play( &mut pad,
+		&[
+			(  0, Pressed ( LeftTrigger ) ),
+			( 10, Pressed ( South       ) ),
+			( 20, Released( LeftTrigger ) ),
+			( 30, Released( South       ) ),
+		]);
+
+		assert_eq!( events, vec![ down( 'b' ), up( 'b' ) ] );
+	}
+
+
+
+	#[ test ]
+	//
+	fn profile_switch_releases_held_keys()
+	{
+		let config = config( "
+			one: { South: [ Button: a ], Select: [ SwitchProfile: two ] }
+			two: { South: [ Button: b ], Select: [ SwitchProfile: one ] }
+		");
+
+		let mut pad = pad( &config, "one" );
+
+		let mut events = play( &mut pad,
+		&[
+			(  0, Pressed ( South  ) ),
+			( 10, Pressed ( Select ) ),
+			( 20, Released( Select ) ),
+		]);
+
+		assert_eq!( pad.take_switch(), Some( ProfileSwitch::To( "two".to_string() ) ) );
+
+		let mut out = RecordOutput::new();
+
+		pad.reconfigure( config.profile( "two" ).unwrap(), &config, at( 20 ), &mut out ).unwrap();
+		events.extend( out.events );
+
+
+		// The release of South belongs to a press the new profile never saw.
+		//
+		events.extend( play( &mut pad,
+		&[
+			( 30, Released( South ) ),
+			( 40, Pressed ( South ) ),
+			( 50, Released( South ) ),
+		]));
+
+		assert_eq!( events, vec![ down( 'a' ), up( 'a' ), down( 'b' ), up( 'b' ) ] );
+	}
+
+
+
+	// A stick that is held still keeps moving the mouse after a switch, it sends no new event.
+	//
+	#[ test ]
+	//
+	fn profile_switch_keeps_sticks()
+	{
+		let config = config( "
+			one: { RightStickX: [ MouseX: 10.0 ], Select: [ SwitchProfile: two ] }
+			two: { RightStickX: [ MouseX: 10.0 ], RightStick: [ Deadzone: { inner: 0.1 } ] }
+		");
+
+		let mut pad = pad( &config, "one" );
+
+		play( &mut pad,
+		&[
+			(  0, EventType::AxisChanged( RightStickX, 1.0 ) ),
+			(  0, Pressed ( Select ) ),
+			( 10, Released( Select ) ),
+		]);
+
+		let mut out = RecordOutput::new();
+
+		pad.take_switch();
+		pad.reconfigure( config.profile( "two" ).unwrap(), &config, at( 10 ), &mut out ).unwrap();
+
+		let events = play( &mut pad, &[ ( 15, NoChange ), ( 20, NoChange ) ] );
+
+		assert!( !events.is_empty() );
+		assert!( events.iter().all( |e| match e { OutputEvent::MouseMove( x, 0 ) => *x > 0, _ => false } ), "{:?}", events );
+	}
+
+
+
+	#[ test ]
+	//
+	fn failed_switch_keeps_profile()
+	{
+		let config = config( "
+			one: { South: [ Button: a ] }
+			two: { South: [ Button: a ], East: [ Button: NoSuchKey ] }
+		");
+
+		let mut pad = pad( &config, "one" );
+		let mut out = RecordOutput::new();
+
+		assert!( pad.reconfigure( config.profile( "two" ).unwrap(), &config, at( 20 ), &mut out ).is_err() );
+
+		let events = play( &mut pad, &[ ( 0, Pressed( South ) ), ( 10, Released( South ) ) ] );
+
+		assert_eq!( events, vec![ down( 'a' ), up( 'a' ) ] );
+	}
+
+
+
+	#[ test ]
+	//
+	fn combo()
+	{
+		let config = config( "
+			p: { South + East: [ Button: c ], South: [ Button: a ] }
+		");
+
+		let mut pad = pad( &config, "p" );
+
+		let events = play( &mut pad,
+		&[
+			(   0, Pressed ( South ) ),
+			(  10, Pressed ( East  ) ),
+			(  20, NoChange          ),
+			(  30, Released( East  ) ),
+			(  40, Released( South ) ),
+
+			// Alone, South is delivered once the window expires.
+			//
+			( 100, Pressed ( South ) ),
+			( 120, NoChange          ),
+			( 160, NoChange          ),
+			( 170, Released( South ) ),
+		]);
+
+		assert_eq!( events, vec![ down( 'c' ), up( 'c' ), down( 'a' ), up( 'a' ) ] );
+	}
+
+
+
+	#[ test ]
+	//
+	fn largest_combo_wins()
+	{
+		let config = config( "
+			p:
+				South + East       : [ Button: c ]
+				South + East + West: [ Button: d ]
+		");
+
+		let mut pad = pad( &config, "p" );
+
+		let events = play( &mut pad,
+		&[
+			(   0, Pressed ( South ) ),
+			(  10, Pressed ( East  ) ),
+			(  20, Pressed ( West  ) ),
+			(  30, Released( West  ) ),
+			(  40, Released( East  ) ),
+			(  50, Released( South ) ),
+
+			// Without West the smaller combo fires when the window expires.
+			//
+			( 100, Pressed ( East  ) ),
+			( 110, Pressed ( South ) ),
+			( 160, NoChange          ),
+			( 170, Released( South ) ),
+			( 180, Released( East  ) ),
+		]);
+
+		assert_eq!( events, vec![ down( 'd' ), up( 'd' ), down( 'c' ), up( 'c' ) ] );
+	}
+
+
+
+	#[ test ]
+	//
+	fn multitap()
+	{
+		let config = config( "
+			p: { South: [ MultiTap: { taps: [ a, b ], window_ms: 100, hold_ms: 50 } ] }
+		");
+
+		let mut pad = pad( &config, "p" );
+
+		let events = play( &mut pad,
+		&[
+			// A double tap reaches the last key and sends it right away.
+			//
+			(   0, Pressed ( South ) ),
+			(  10, Released( South ) ),
+			(  50, Pressed ( South ) ),
+			(  60, Released( South ) ),
+
+			// A single tap is sent when the window expires.
+			//
+			( 200, Pressed ( South ) ),
+			( 210, Released( South ) ),
+			( 300, NoChange          ),
+			( 320, NoChange          ),
+
+			// A long press is not a tap.
+			//
+			( 400, Pressed ( South ) ),
+			( 500, Released( South ) ),
+			( 700, NoChange          ),
+		]);
+
+		assert_eq!( events, vec![ down( 'b' ), up( 'b' ), down( 'a' ), up( 'a' ) ] );
+	}
+
+
+
+	#[ test ]
+	//
+	fn tap_hold()
+	{
+		let config = config( "
+			p: { South: [ TapHold: { tap: a, hold: b, threshold_ms: 100 } ] }
+		");
+
+		let mut pad = pad( &config, "p" );
+
+		let events = play( &mut pad,
+		&[
+			(   0, Pressed ( South ) ),
+			(  50, NoChange          ),
+			(  60, Released( South ) ),
+
+			( 200, Pressed ( South ) ),
+			( 250, NoChange          ),
+			( 310, NoChange          ),
+			( 400, NoChange          ),
+			( 500, Released( South ) ),
+		]);
+
+		assert_eq!( events, vec![ down( 'a' ), up( 'a' ), down( 'b' ), up( 'b' ) ] );
+	}
+
+
+
+	#[ test ]
+	//
+	fn combo_with_stick()
+	{
+		let config = config( "
+			p: { South + LeftStickX: [ Button: a ] }
+		");
+
+		match Gamepad::new().configure( config.profile( "p" ).unwrap() )
+		{
+			Err( PadError::NotInCombo( LeftStickX ) ) => (),
+			other                                     => panic!( "{:?}", other ),
+		}
+	}
+
+
+
+	// Acceleration needs to know how far the whole stick is pushed.
+	//
+	#[ test ]
+	//
+	fn acceleration_on_axis()
+	{
+		let config = config( "
+			axis : { RightStickX: [ MouseX    : { pixels: 4.0, acceleration: { multiplier: 2.0 } } ] }
+			stick: { RightStick : [ StickMouse: { pixels: 4.0, acceleration: { multiplier: 2.0 } } ] }
+		");
+
+		match Gamepad::new().configure( config.profile( "axis" ).unwrap() )
+		{
+			Err( PadError::BadMouse( _ ) ) => (),
+			other                          => panic!( "{:?}", other ),
+		}
+
+		assert!( Gamepad::new().configure( config.profile( "stick" ).unwrap() ).is_ok() );
+	}
+
+
+
+	// Released after the threshold before a tick saw it held, the hold key still gets pressed.
+	//
+	#[ test ]
+	//
+	fn hold_without_tick()
+	{
+		let config = config( "
+			p: { South: [ TapHold: { tap: a, hold: b, threshold_ms: 100 } ] }
+		");
+
+		let mut pad = pad( &config, "p" );
+
+		let events = play( &mut pad,
+		&[
+			(   0, Pressed ( South ) ),
+			( 150, Released( South ) ),
+			( 160, NoChange          ),
+		]);
+
+		assert_eq!( events, vec![ down( 'b' ), up( 'b' ) ] );
+	}
+
+
+
+	// A press and release in the same poll still start the sequence, the wait is done on the ticks after it.
+	//
+	#[ test ]
+	//
+	fn sequence_quick_press()
+	{
+		let config = config( "
+			base: { South: [ Sequence: { steps: [ Tap: a, Wait: 100, Tap: b ] } ] }
+		");
+
+		let mut pad = pad( &config, "base" );
+
+		let events = play( &mut pad,
+		&[
+			(   0, Pressed ( South ) ),
+			(   0, Released( South ) ),
+			(  50, NoChange          ),
+			( 100, NoChange          ),
+		]);
+
+		assert_eq!( events, vec![ down( 'a' ), up( 'a' ), down( 'b' ), up( 'b' ) ] );
+	}
+
+
+
+	#[ test ]
+	//
+	fn sequence_without_steps()
+	{
+		let config = config( "
+			base: { South: [ Sequence: { steps: [] } ] }
+		");
+
+		match Gamepad::new().configure( config.profile( "base" ).unwrap() )
+		{
+			Err( PadError::EmptySequence ) => (),
+			other                          => panic!( "{:?}", other ),
+		}
+	}
+}
+
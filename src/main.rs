@@ -14,17 +14,19 @@
 //   - test with a realistic profile in swtor
 //   - provide more possible actions
 //   - test command line parameters
-//   - support multiple pads
 //   - document
-//   - error handling, remove all unwraps
 //   - unit test
 //   - publish
 //
 
 use std::convert::TryFrom;
-use std::path::PathBuf;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, atomic::{ AtomicBool, Ordering } };
+use std::process;
 use failure::Error;
 use std::env;
+use std::io;
+use std::fs;
 
 
 use libpadawan::*;
@@ -32,7 +34,18 @@ use clap::{ App, load_yaml };
 
 fn main()
 {
+	if let Err( e ) = run()
+	{
+		eprintln!( "Error: {}", e );
 
+		process::exit( 1 );
+	}
+}
+
+
+
+fn run() -> Result< (), Error >
+{
 	// Command line options:
 	//
 	// The YAML file is found relative to the current file, similar to how modules are found
@@ -40,41 +53,169 @@ fn main()
 	let yaml    = load_yaml!( "clap.yml" );
 	let matches = App::from_yaml( yaml ).get_matches();
 
+
+	// Recording doesn't need a configuration.
+	//
+	if let Some( rec ) = matches.subcommand_matches( "record" )
+	{
+		let file       = rec.value_of( "file" ).unwrap();
+		let mut source = GilrsSource::new()?;
+		let running    = Arc::new( AtomicBool::new( true ) );
+
+		stop_on_signal( running.clone() )?;
+
+		return record( &mut source, Path::new( file ), &running )
+
+			.map_err( |e| PadError::Record( file.to_string(), e.to_string() ).into() )
+		;
+	}
+
+
+	// Checking a configuration doesn't need gamepads or an output either.
+	//
+	if let Some( check ) = matches.subcommand_matches( "check" )
+	{
+		let cfg_file = check.value_of( "config" ).or( matches.value_of( "config" ) ).unwrap_or( "config.yml" );
+		let path     = abs_path( cfg_file ).map_err( |e| PadError::io( Path::new( cfg_file ), e ) )?;
+		let yaml     = fs::read_to_string( &path ).map_err( |e| PadError::io( &path, e ) )?;
+		let problems = check_config( &yaml );
+
+		for problem in &problems
+		{
+			println!( "{}:{}", cfg_file, problem );
+		}
+
+
+		if problems.is_empty()
+		{
+			println!( "{}: no problems found", cfg_file );
+			return Ok(());
+		}
+
+		return Err( PadError::Problems( problems.len(), cfg_file.to_string() ).into() );
+	}
+
+
+	let replay = matches.subcommand_matches( "replay" );
+
 	// Gets a value for config if supplied by user, or defaults to "default.conf"
 	//
 	let cfg_file = matches.value_of( "config" ).unwrap_or( "config.yml" );
 	println!( "Value for config: {}", cfg_file );
 
-	let prof_cfg = matches.value_of( "profile" ).unwrap_or( "swtor_basics" );
-	println!( "Value for config: {}", prof_cfg );
+	let prof_cfg = replay.map( |r| r.value_of( "profile" ) ).unwrap_or( matches.value_of( "profile" ) );
+	println!( "Value for profile: {:?}", prof_cfg );
 
 
 	//-----------------------------------------------------------------------------
 
-	let config =
+	let path       = abs_path( cfg_file ).map_err( |e| PadError::io( Path::new( cfg_file ), e ) )?;
+	let mut config = Config::try_from( path )?;
+
+
+
+
+	// When replaying, every recorded pad uses the profile from the command line.
+	//
+	if replay.is_some() { config.pads_mut().clear() }
+
 
-		Config::try_from
-		(
+	let backend = match matches.value_of( "output" )
+	{
+		Some( "uinput" ) => Backend::Uinput                           ,
+		Some( _        ) => Backend::Enigo                            ,
+		None             => config.output().unwrap_or( Backend::Enigo ),
+	};
 
-			 abs_path( cfg_file )
-			.expect  ( &format!( "Could not find configuration file: {}", cfg_file ) )
 
-		).expect( "Could not parse config file" )
+	// Make sure every profile we might load exists and maps on a gamepad before we start listening,
+	// including the ones we can switch to at runtime.
+	//
+	let mut names: Vec< &str > = prof_cfg.into_iter().chain( config.pads().iter().map( |p| p.1.as_ref() ) ).collect();
+	let mut i = 0;
+
+	while let Some( &name ) = names.get( i )
+	{
+		let profile = config.profile( name ).ok_or_else( || PadError::UnknownProfile( name.to_string() ) )?;
+
+		Gamepad::new().configure( profile )
+
+			.map_err( |e| PadError::in_profile( name, e ) )?
+		;
+
+
+		#[ cfg( target_os = "linux" ) ]
+		//
+		if backend == Backend::Uinput
+		{
+			for action in profile.values().flatten()
+			{
+				check_keys( action, config.layout() )
+
+					.map_err( |e| PadError::in_profile( name, e ) )?
+				;
+			}
+		}
+
+		for target in profile.values().flatten().flat_map( |a| a.profiles() )
+		{
+			if !names.contains( &target.as_ref() ) { names.push( target ) }
+		}
+
+		i += 1;
+	}
+
+
+	if prof_cfg.is_none() && config.pads().is_empty()
+	{
+		return Err( PadError::NoProfile.into() );
+	}
+
+
+	let out: Box< dyn Output > = if matches.is_present( "dry-run" ) { Box::new( DryRunOutput::new() ) }
+
+		else { backend.open( config.layout() ).map_err( |e| PadError::Output( backend, e.to_string() ) )? }
 	;
 
 
+	let mut pads = Pads::new( config, prof_cfg.map( |p| p.to_string() ), out );
+
+	stop_on_signal( pads.running() )?;
+
+
+	if let Some( replay ) = replay
+	{
+		let file       = replay.value_of( "file" ).unwrap();
+		let mut source = ReplaySource::open( Path::new( file ) )
+
+			.map_err( |e| PadError::Recording( file.to_string(), e.to_string() ) )?
+		;
+
+		pads.listen( &mut source );
+	}
 
+	else
+	{
+		let mut source = GilrsSource::new()?;
 
-	let mut     pad = Gamepad::new();
-	let     profile = config.profiles().get( prof_cfg ).expect( &format!( "Profile <{}> not found in configuration file!", prof_cfg ) );
+		pads.listen( &mut source );
+	}
 
-	pad.configure( profile );
 
-	let mut all_pads = vec![ pad ];
+	println!( "Released all keys, bye." );
 
-	Gamepad::listen( &mut all_pads );
+	Ok(())
+}
+
+
+
+// Ctrl+C or SIGTERM make the event loop return, so held keys get released before we exit.
+//
+fn stop_on_signal( running: Arc< AtomicBool > ) -> Result< (), Error >
+{
+	ctrlc::set_handler( move || running.store( false, Ordering::SeqCst ) )
 
-	println!("{:?}", all_pads.first() );
+		.map_err( |e| PadError::Signal( e.to_string() ).into() )
 }
 
 
@@ -83,7 +224,7 @@ fn main()
 
 // Takes a relative path and returns an absolute one, searching in the cwd, then relative to the executable file.
 //
-pub fn abs_path( relative: &str ) -> Result< PathBuf, Error >
+pub fn abs_path( relative: &str ) -> io::Result< PathBuf >
 {
 	let entry = env::current_dir()?.as_path().join( relative );
 
@@ -93,7 +234,7 @@ pub fn abs_path( relative: &str ) -> Result< PathBuf, Error >
 
 
 	let exe    = env::current_exe()?;
-	let parent = exe.parent().ok_or( std::io::Error::new( std::io::ErrorKind::NotFound, "Program executable has no parent directory" ) )?;
+	let parent = exe.parent().ok_or( io::Error::new( io::ErrorKind::NotFound, "Program executable has no parent directory" ) )?;
 
    // Canonicalize will throw an error if the file doesn't exist
    //
@@ -0,0 +1,333 @@
+// The Output trait is where actions send their keyboard and mouse events.
+// This allows us to swap the library that injects events into the OS without touching the actions.
+//
+
+
+use std::  fmt          ;
+use std:: time::Instant;
+
+use super::*;
+
+use failure  ::Error;
+use ::enigo::{ Enigo, KeyboardControllable, MouseControllable };
+
+
+
+/// A sink for synthesized keyboard and mouse events.
+///
+pub trait Output : fmt::Debug
+{
+	fn press     ( &mut self, key: Clickable );
+	fn release   ( &mut self, key: Clickable );
+	fn move_mouse( &mut self, x: i32, y: i32 );
+
+	/// Turn the mouse wheel by a number of clicks. Positive is right and up.
+	///
+	fn scroll( &mut self, x: i32, y: i32 );
+
+	/// Tells the output which gamepad input the following events come from.
+	///
+	fn source( &mut self, _input: InputID ) {}
+}
+
+
+
+impl Backend
+{
+	/// Create the output for this backend.
+	///
+	pub fn open( &self, layout: Layout ) -> Result< Box< dyn Output >, Error >
+	{
+		match self
+		{
+			Backend::Enigo  => Ok( Box::new( EnigoOutput::new() ) ),
+
+			#[ cfg( target_os = "linux" ) ]
+			//
+			Backend::Uinput => Ok( Box::new( UinputOutput::new( layout )? ) ),
+
+			#[ cfg( not( target_os = "linux" ) ) ]
+			//
+			Backend::Uinput => Err( failure::err_msg( "The uinput output is only available on Linux" ) ),
+		}
+	}
+}
+
+
+
+/// The keys that are held down, and the input that pressed them.
+///
+#[ derive( Debug, Clone, Default ) ]
+//
+pub struct Held( Vec< ( InputID, Clickable ) > );
+
+
+
+impl Held
+{
+	/// Release all held keys, in the reverse order they were pressed in.
+	///
+	pub fn release_all( &mut self, out: &mut dyn Output )
+	{
+		for ( input, key ) in self.0.drain( .. ).rev()
+		{
+			out.source ( input );
+			out.release( key   );
+		}
+	}
+
+
+	/// Release the keys held by one input, in the reverse order they were pressed in.
+	///
+	pub fn release_input( &mut self, input: InputID, out: &mut dyn Output )
+	{
+		let ( mine, others ) = self.0.drain( .. ).partition( |( i, _ )| *i == input );
+
+		self.0 = others;
+
+		Held( mine ).release_all( out );
+	}
+}
+
+
+
+/// Passes everything on to another output while keeping track of the keys that are held down.
+///
+#[ derive( Debug ) ]
+//
+pub struct Tracker<'a>
+{
+	out   : &'a mut dyn Output,
+	held  : &'a mut Held      ,
+	source: InputID           ,
+}
+
+
+
+impl<'a> Tracker<'a>
+{
+	pub fn new( out: &'a mut dyn Output, held: &'a mut Held ) -> Self
+	{
+		Self { out, held, source: InputID::Unknown }
+	}
+}
+
+
+
+impl<'a> Output for Tracker<'a>
+{
+	fn press( &mut self, key: Clickable )
+	{
+		let entry = ( self.source, key );
+
+		if !self.held.0.contains( &entry ) { self.held.0.push( entry ) }
+
+		self.out.press( key );
+	}
+
+
+	fn release( &mut self, key: Clickable )
+	{
+		let source = self.source;
+
+		self.held.0.retain( |&( input, k )| input != source || k != key );
+
+		self.out.release( key );
+	}
+
+
+	fn move_mouse( &mut self, x: i32, y: i32 )
+	{
+		self.out.move_mouse( x, y );
+	}
+
+
+	fn scroll( &mut self, x: i32, y: i32 )
+	{
+		self.out.scroll( x, y );
+	}
+
+
+	fn source( &mut self, input: InputID )
+	{
+		self.source = input;
+		self.out.source( input );
+	}
+}
+
+
+
+
+/// Injects events in the OS with the enigo library. This is the default output.
+///
+pub struct EnigoOutput
+{
+	enigo: Enigo
+}
+
+
+
+impl EnigoOutput
+{
+	pub fn new() -> Self
+	{
+		Self { enigo: Enigo::new() }
+	}
+}
+
+
+
+impl Output for EnigoOutput
+{
+	fn press( &mut self, key: Clickable )
+	{
+		match key
+		{
+			Clickable::Keyboard( key ) => self.enigo.key_down  ( key ),
+			Clickable::Mouse   ( key ) => self.enigo.mouse_down( key ),
+		}
+	}
+
+
+	fn release( &mut self, key: Clickable )
+	{
+		match key
+		{
+			Clickable::Keyboard( key ) => self.enigo.key_up  ( key ),
+			Clickable::Mouse   ( key ) => self.enigo.mouse_up( key ),
+		}
+	}
+
+
+	fn move_mouse( &mut self, x: i32, y: i32 )
+	{
+		self.enigo.mouse_move_relative( x, y );
+	}
+
+
+	// For enigo a positive y scrolls down.
+	//
+	fn scroll( &mut self, x: i32, y: i32 )
+	{
+		if x != 0 { self.enigo.mouse_scroll_x(  x ) }
+		if y != 0 { self.enigo.mouse_scroll_y( -y ) }
+	}
+}
+
+
+
+// Enigo doesn't implement Debug.
+//
+impl fmt::Debug for EnigoOutput
+{
+	fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result
+	{
+		write!( f, "EnigoOutput" )
+	}
+}
+
+
+
+
+/// Something that was sent to an output.
+///
+#[ derive( Debug, Clone, Copy, PartialEq ) ]
+//
+pub enum OutputEvent
+{
+	Press    ( Clickable ),
+	Release  ( Clickable ),
+	MouseMove( i32, i32  ),
+	Scroll   ( i32, i32  ),
+}
+
+
+
+/// Remembers everything that is sent to it instead of injecting it in the OS. Meant for tests.
+///
+#[ derive( Debug, Clone, Default ) ]
+//
+pub struct RecordOutput
+{
+	pub events: Vec< OutputEvent >
+}
+
+
+
+impl RecordOutput
+{
+	pub fn new() -> Self
+	{
+		Self::default()
+	}
+}
+
+
+
+impl Output for RecordOutput
+{
+	fn press     ( &mut self, key: Clickable ) { self.events.push( OutputEvent::Press    ( key  ) ) }
+	fn release   ( &mut self, key: Clickable ) { self.events.push( OutputEvent::Release  ( key  ) ) }
+	fn move_mouse( &mut self, x: i32, y: i32 ) { self.events.push( OutputEvent::MouseMove( x, y ) ) }
+	fn scroll    ( &mut self, x: i32, y: i32 ) { self.events.push( OutputEvent::Scroll   ( x, y ) ) }
+}
+
+
+
+
+/// Logs everything that is sent to it in a human readable form instead of injecting it in the OS.
+/// This allows testing a profile from a terminal without typing into whatever window has focus.
+///
+#[ derive( Debug, Clone ) ]
+//
+pub struct DryRunOutput
+{
+	start : Instant,
+	source: InputID,
+}
+
+
+
+impl DryRunOutput
+{
+	pub fn new() -> Self
+	{
+		Self { start: Instant::now(), source: InputID::Unknown }
+	}
+
+
+	fn log( &self, what: fmt::Arguments<'_> )
+	{
+		let t = self.start.elapsed();
+
+		println!( "[{:>5}.{:03}] {:<13} {}", t.as_secs(), t.subsec_millis(), format!( "{:?}", self.source ), what );
+	}
+}
+
+
+
+impl Output for DryRunOutput
+{
+	fn press  ( &mut self, key: Clickable ) { self.log( format_args!( "press   {:?}", key ) ) }
+	fn release( &mut self, key: Clickable ) { self.log( format_args!( "release {:?}", key ) ) }
+
+
+	// Mouse actions run on every tick, so don't flood the log when they don't move.
+	//
+	fn move_mouse( &mut self, x: i32, y: i32 )
+	{
+		if x != 0 || y != 0 { self.log( format_args!( "move    {:>4}, {:>4}", x, y ) ) }
+	}
+
+
+	fn scroll( &mut self, x: i32, y: i32 )
+	{
+		if x != 0 || y != 0 { self.log( format_args!( "scroll  {:>4}, {:>4}", x, y ) ) }
+	}
+
+
+	fn source( &mut self, input: InputID )
+	{
+		self.source = input;
+	}
+}
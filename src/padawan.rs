@@ -15,7 +15,27 @@ use serde_derive::{ Serialize, Deserialize };
 mod gamepad;
 mod config;
 mod action;
+mod pads;
+mod output;
+mod source;
+mod record;
+mod error;
+mod check;
+mod curve;
+mod stick;
+
+#[ cfg( target_os = "linux" ) ] mod uinput;
 
 pub use self::gamepad::*;
 pub use self::config::*;
 pub use self::action::*;
+pub use self::pads::*;
+pub use self::output::*;
+pub use self::source::*;
+pub use self::record::*;
+pub use self::error::*;
+pub use self::check::*;
+pub use self::curve::*;
+pub use self::stick::*;
+
+#[ cfg( target_os = "linux" ) ] pub use self::uinput::*;
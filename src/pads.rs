@@ -0,0 +1,509 @@
+// Routes the events of every connected controller to the Gamepad object that was configured for it.
+// Each physical pad gets it's own Gamepad with it's own profile, chosen by the `pads` section of the config.
+//
+
+
+use std::        time::{ Duration, SystemTime }            ;
+use std:: collections::HashMap                              ;
+use std::        sync::{ Arc, atomic::{ AtomicBool, Ordering } };
+use std::      thread                                       ;
+
+use super::*;
+
+
+
+
+/// What we know about a physical gamepad. This is what the selectors in the `pads` section of the config match against.
+///
+#[ derive( Debug, Clone, PartialEq ) ]
+//
+pub struct PadInfo
+{
+	pub id  : usize ,
+	pub name: String,
+	pub uuid: String,
+}
+
+
+
+/// All the gamepads we drive, indexed by the gilrs gamepad id.
+///
+#[ derive( Debug ) ]
+//
+pub struct Pads
+{
+	config : Config                   ,
+	default: Option< String >         ,
+	pads   : HashMap< usize, Gamepad >,
+	active : HashMap< usize, String  >,
+	out    : Box< dyn Output >        ,
+	running: Arc< AtomicBool >        ,
+}
+
+
+
+impl Pads
+{
+	/// The default profile is used for pads that match no entry in the `pads` section of the config.
+	/// Without a default, those pads are ignored. All gamepads send their keyboard and mouse events to `out`.
+	///
+	pub fn new( config: Config, default: Option< String >, out: Box< dyn Output > ) -> Self
+	{
+		Self
+		{
+			config : config                           ,
+			default: default                          ,
+			pads   : HashMap::new()                   ,
+			active : HashMap::new()                   ,
+			out    : out                              ,
+			running: Arc::new( AtomicBool::new( true ) ),
+		}
+	}
+
+
+
+	/// A flag that makes `listen` return when it is set to false, eg. from a signal handler.
+	///
+	pub fn running( &self ) -> Arc< AtomicBool >
+	{
+		self.running.clone()
+	}
+
+
+
+	/// Runs the event loop on an input source. Blocks the current thread until the source is done,
+	/// which for physical gamepads means forever, or until the running flag is set to false.
+	/// Everything that is still held down is released before returning.
+	///
+	pub fn listen( &mut self, source: &mut dyn InputSource )
+	{
+		// Pads that are already plugged in when we start.
+		//
+		for info in source.pads()
+		{
+			self.try_attach( &info );
+		}
+
+
+		while !source.done() && self.running.load( Ordering::SeqCst )
+		{
+			self.poll( source );
+
+			thread::sleep( Duration::from_millis ( Gamepad::SLEEP_MS ) );
+		}
+
+
+		self.release_all();
+	}
+
+
+
+	/// Release every key and mouse button held by any of our gamepads.
+	///
+	pub fn release_all( &mut self )
+	{
+		for pad in self.pads.values_mut()
+		{
+			pad.release_all( self.out.as_mut() );
+		}
+	}
+
+
+
+	/// Process all pending events from the source, followed by a `NoChange` tick for every gamepad.
+	///
+	pub fn poll( &mut self, source: &mut dyn InputSource )
+	{
+		while let Some( PadEvent { pad, event, time } ) = source.next_event()
+		{
+			// New pads normally announce themselves with a Connected event, but not every source does.
+			// A pad we don't drive that goes away has nothing to attach.
+			//
+			let gone = match event { EventType::Disconnected | EventType::Dropped => true, _ => false };
+
+			if !gone && !self.pads.contains_key( &pad )
+			{
+				self.try_attach( &source.pad_info( pad ).unwrap_or( PadInfo::unknown( pad ) ) );
+			}
+
+			self.process_event( pad, event, time );
+		}
+
+
+		let now = SystemTime::now();
+
+		for pad in self.pads.values_mut()
+		{
+			pad.process_event( EventType::NoChange, now, self.out.as_mut() );
+		}
+	}
+
+
+
+	/// Create and configure a Gamepad for a physical pad, if the config has a profile for it.
+	/// Returns whether the pad will be driven. Pads that are already attached are left alone. gilrs gives
+	/// a reconnecting pad it's old id back, so it finds the Gamepad it had before with it's profile.
+	///
+	pub fn attach( &mut self, info: &PadInfo ) -> Result< bool, PadError >
+	{
+		if self.pads.contains_key( &info.id ) { return Ok( true ) }
+
+
+		let name = match self.config.profile_for( info ).or( self.default.as_ref().map( |d| d.as_ref() ) )
+		{
+			Some( name ) => name.to_string(),
+			None         => return Ok( false ),
+		};
+
+
+		let profile = self.config.profile( &name ).ok_or_else( || PadError::UnknownProfile( name.clone() ) )?;
+		let mut pad = Gamepad::new();
+
+		pad.set_combo_window( self.config.combo_window() );
+		pad.configure( profile )?;
+		pad.configure_layers( &self.config )?;
+
+		println!( "Gamepad {} ({}) uses profile: {}", info.id, info.name, name );
+
+		self.pads  .insert( info.id, pad  );
+		self.active.insert( info.id, name );
+
+		Ok( true )
+	}
+
+
+
+	// The event loop shouldn't die because one pad has a broken profile, the others keep working.
+	//
+	fn try_attach( &mut self, info: &PadInfo )
+	{
+		if let Err( e ) = self.attach( info )
+		{
+			eprintln!( "Gamepad {} ({}) is ignored: {}", info.id, info.name, e );
+		}
+	}
+
+
+
+	/// Send an event to the Gamepad of the physical pad it came from. Events from pads we don't drive are dropped.
+	///
+	pub fn process_event( &mut self, id: usize, event: EventType, time: SystemTime )
+	{
+		if let Some( pad ) = self.pads.get_mut( &id )
+		{
+			if let EventType::Disconnected = event
+			{
+				println!( "Gamepad {} disconnected, releasing all keys", id );
+			}
+
+			pad.process_event( event, time, self.out.as_mut() );
+
+			if let Some( switch ) = pad.take_switch() { self.switch( id, switch, time ) }
+		}
+	}
+
+
+
+	/// Change the profile of a gamepad at `time`. Keys held by the old profile are released.
+	///
+	pub fn switch( &mut self, id: usize, switch: ProfileSwitch, time: SystemTime )
+	{
+		let name = match switch
+		{
+			ProfileSwitch::To( name ) => name,
+
+			ProfileSwitch::Cycle( names ) =>
+			{
+				let next = match self.active.get( &id ).and_then( |a| names.iter().position( |n| n == a ) )
+				{
+					Some( i ) => ( i + 1 ) % names.len(),
+					None      => 0                      ,
+				};
+
+				match names.into_iter().nth( next )
+				{
+					Some( name ) => name  ,
+					None         => return,
+				}
+			}
+		};
+
+
+		let pad = match self.pads.get_mut( &id )
+		{
+			Some( pad ) => pad   ,
+			None        => return,
+		};
+
+
+		let config = &self.config;
+
+		let result = match config.profile( &name )
+		{
+			Some( profile ) => pad.reconfigure( profile, config, time, self.out.as_mut() ),
+			None            => Err( PadError::UnknownProfile( name.clone() ) ),
+		};
+
+
+		match result
+		{
+			Ok ( _ ) =>
+			{
+				println!( "Gamepad {} switched to profile: {}", id, name );
+
+				self.active.insert( id, name );
+			}
+
+			Err( e ) => eprintln!( "Gamepad {} could not switch profiles: {}", id, e ),
+		}
+	}
+
+
+
+	pub fn pad( &self, id: usize ) -> Option< &Gamepad >
+	{
+		self.pads.get( &id )
+	}
+}
+
+
+
+impl PadInfo
+{
+	/// For pads the source knows nothing about. They can only be matched by id.
+	///
+	pub fn unknown( id: usize ) -> Self
+	{
+		Self
+		{
+			id  : id           ,
+			name: String::new(),
+			uuid: String::new(),
+		}
+	}
+}
+
+
+
+
+#[ cfg( test ) ]
+//
+mod tests
+{
+	use super::*;
+	use std::{ cell::RefCell, rc::Rc, time::UNIX_EPOCH };
+	use enigo::Key;
+	use self::EventType::{ ButtonPressed as Pressed, ButtonReleased as Released };
+
+
+	// Pads owns it's output, this one lets the test look at what was sent.
+	//
+	#[ derive( Debug, Clone, Default ) ]
+	//
+	struct Shared( Rc< RefCell< RecordOutput > > );
+
+	impl Output for Shared
+	{
+		fn press     ( &mut self, key: Clickable ) { self.0.borrow_mut().press     ( key  ) }
+		fn release   ( &mut self, key: Clickable ) { self.0.borrow_mut().release   ( key  ) }
+		fn move_mouse( &mut self, x: i32, y: i32 ) { self.0.borrow_mut().move_mouse( x, y ) }
+		fn scroll    ( &mut self, x: i32, y: i32 ) { self.0.borrow_mut().scroll    ( x, y ) }
+	}
+
+
+	struct Test
+	{
+		pads: Pads  ,
+		out : Shared,
+	}
+
+
+	impl Test
+	{
+		// The profiles are the same in every test, `a` types an a and `b` types a b. Yaml doesn't allow tabs.
+		//
+		fn new( pads: &str, default: Option< &str > ) -> Self
+		{
+			let yaml = format!( "{}\na: {{ South: [ Button: a ] }}\nb: {{ South: [ Button: b ] }}", pads );
+
+			let config = serde_yaml::from_str( &yaml.replace( '\t', " " ) ).unwrap();
+			let out    = Shared::default();
+
+			Self { pads: Pads::new( config, default.map( |d| d.to_string() ), Box::new( out.clone() ) ), out }
+		}
+
+
+		// Run the event loop on a script from the two pads of `infos` and return what was sent.
+		//
+		fn listen( &mut self, events: &[ ( usize, EventType ) ] ) -> Vec< OutputEvent >
+		{
+			let events = events.iter().enumerate().map( |( ms, &( pad, event ) )|
+
+				PadEvent { pad, event, time: UNIX_EPOCH + Duration::from_millis( ms as u64 ) }
+
+			).collect();
+
+			self.pads.listen( &mut ScriptSource::new( infos(), events ) );
+
+			self.out.0.borrow_mut().events.drain( .. ).collect()
+		}
+
+
+		// What each pad types when South is pressed.
+		//
+		fn typed( &mut self, id: usize ) -> Vec< OutputEvent >
+		{
+			self.listen( &[ ( id, Pressed( InputID::South ) ), ( id, Released( InputID::South ) ) ] )
+		}
+	}
+
+
+	fn infos() -> Vec< PadInfo >
+	{
+		vec!
+		[
+			PadInfo { id: 0, name: "Xbox 360 Pad".to_string(), uuid: "030000005e0400008e02000014010000".to_string() },
+			PadInfo { id: 1, name: "Sony PS3 Pad".to_string(), uuid: "030000004c0500006802000011010000".to_string() },
+		]
+	}
+
+
+	fn key( c: char ) -> Vec< OutputEvent >
+	{
+		let key = Clickable::Keyboard( Key::Layout( c ) );
+
+		vec![ OutputEvent::Press( key ), OutputEvent::Release( key ) ]
+	}
+
+
+
+	#[ test ]
+	//
+	fn by_id()
+	{
+		let mut test = Test::new( "pads: [ [ Id: 1, b ], [ Any, a ] ]", None );
+
+		assert_eq!( test.typed( 0 ), key( 'a' ) );
+		assert_eq!( test.typed( 1 ), key( 'b' ) );
+	}
+
+
+
+	#[ test ]
+	//
+	fn by_name()
+	{
+		let mut test = Test::new( "pads: [ [ Name: Sony PS3 Pad, b ], [ Any, a ] ]", None );
+
+		assert_eq!( test.typed( 0 ), key( 'a' ) );
+		assert_eq!( test.typed( 1 ), key( 'b' ) );
+	}
+
+
+
+	// Uuids are compared without case.
+	//
+	#[ test ]
+	//
+	fn by_uuid()
+	{
+		let mut test = Test::new( "pads: [ [ Uuid: 030000004C0500006802000011010000, b ], [ Any, a ] ]", None );
+
+		assert_eq!( test.typed( 0 ), key( 'a' ) );
+		assert_eq!( test.typed( 1 ), key( 'b' ) );
+	}
+
+
+
+	// The first entry that matches wins, Any takes every pad that is left.
+	//
+	#[ test ]
+	//
+	fn any()
+	{
+		let mut test = Test::new( "pads: [ [ Any, b ], [ Id: 0, a ] ]", None );
+
+		assert_eq!( test.typed( 0 ), key( 'b' ) );
+		assert_eq!( test.typed( 1 ), key( 'b' ) );
+	}
+
+
+
+	#[ test ]
+	//
+	fn default_profile()
+	{
+		let mut test = Test::new( "pads: [ [ Id: 1, b ] ]", Some( "a" ) );
+
+		assert_eq!( test.typed( 0 ), key( 'a' ) );
+		assert_eq!( test.typed( 1 ), key( 'b' ) );
+	}
+
+
+
+	#[ test ]
+	//
+	fn ignored_pad()
+	{
+		let mut test = Test::new( "pads: [ [ Id: 1, b ] ]", None );
+
+		assert_eq!( test.typed( 0 ), vec![] );
+		assert_eq!( test.typed( 1 ), key( 'b' ) );
+
+		assert!( test.pads.pad( 0 ).is_none() );
+	}
+
+
+
+	// A pad we have never seen that goes away is not attached just to be dropped again.
+	//
+	#[ test ]
+	//
+	fn unknown_pad_disconnects()
+	{
+		let mut test = Test::new( "pads: [ [ Any, a ] ]", None );
+
+		test.listen( &[ ( 2, EventType::Disconnected ), ( 3, EventType::Dropped ) ] );
+
+		assert!( test.pads.pad( 2 ).is_none() );
+		assert!( test.pads.pad( 3 ).is_none() );
+	}
+
+
+
+	// Disconnecting releases what the pad held, the order depends on the inputs.
+	// When the pad comes back with the same id, it still has the profile it switched to.
+	//
+	#[ test ]
+	//
+	fn reconnect()
+	{
+		let mut test = Test::new( "pads: [ [ Any, c ] ]\nc: { South: [ Button: a ], North: [ Toggle: t ], Select: [ SwitchProfile: b ] }", None );
+
+		let events = test.listen
+		(&[
+			( 0, Pressed ( InputID::South ) ),
+			( 0, Pressed ( InputID::North ) ),
+			( 0, Released( InputID::North ) ),
+			( 0, EventType::Disconnected    ),
+		]);
+
+		let t = Clickable::Keyboard( Key::Layout( 't' ) );
+
+		assert_eq!( events[ ..2 ], [ key( 'a' )[ 0 ], OutputEvent::Press( t ) ] );
+		assert_eq!( events.len(), 4 );
+		assert!   ( events.contains( &key( 'a' )[ 1 ]           ) );
+		assert!   ( events.contains( &OutputEvent::Release( t ) ) );
+
+
+		test.listen
+		(&[
+			( 0, Pressed ( InputID::Select ) ),
+			( 0, Released( InputID::Select ) ),
+			( 0, EventType::Disconnected     ),
+			( 0, EventType::Connected        ),
+		]);
+
+		assert_eq!( test.typed( 0 ), key( 'b' ) );
+	}
+}
@@ -0,0 +1,185 @@
+// Recording and replaying of gamepad sessions. A recording is a yaml list of events with the time in
+// milliseconds since the recording started. This allows reproducing a mapping problem without the physical pad.
+//
+
+
+use std:: collections::VecDeque                          ;
+use std::        time::{ Duration, SystemTime, Instant }  ;
+use std::          io::Write                             ;
+use std::        path::Path                              ;
+use std::        sync::atomic::{ AtomicBool, Ordering }  ;
+use std::      thread                                    ;
+use std::          fs                                    ;
+
+use super::*;
+
+use failure  ::Error;
+
+
+
+/// One event in a recording.
+///
+#[ derive( Debug, Clone, Copy, PartialEq, Serialize, Deserialize ) ]
+//
+pub struct Record
+{
+	pub ms   : u64      ,
+	pub pad  : usize    ,
+	pub event: EventType,
+}
+
+
+
+/// Write all events from the source to a file until the source is done or `running` is set to false. Every event
+/// is written as soon as it comes in, so the file is usable even if the program gets killed.
+///
+pub fn record( source: &mut dyn InputSource, path: &Path, running: &AtomicBool ) -> Result< (), Error >
+{
+	let mut file  = fs::File::create( path )?;
+	let     start = SystemTime::now();
+
+
+	while !source.done() && running.load( Ordering::SeqCst )
+	{
+		while let Some( PadEvent { pad, event, time } ) = source.next_event()
+		{
+			if let EventType::Dropped = event { continue }
+
+
+			let rec = Record { ms: millis( time.duration_since( start ).unwrap_or( Duration::from_secs( 0 ) ) ), pad, event };
+
+			// Serializing a list with one element gives us exactly one entry to append to the yaml list in the file.
+			//
+			let yaml = serde_yaml::to_string( &[ rec ] )?;
+
+			writeln!( file, "{}", yaml.trim_start_matches( "---\n" ) )?;
+			file.flush()?;
+		}
+
+
+		thread::sleep( Duration::from_millis( Gamepad::SLEEP_MS ) );
+	}
+
+
+	Ok(())
+}
+
+
+
+/// Plays back a recording in real time. The source is done when all events have been delivered.
+///
+#[ derive( Debug, Clone ) ]
+//
+pub struct ReplaySource
+{
+	records: VecDeque< Record >                ,
+	start  : Option< ( Instant, SystemTime ) > ,
+}
+
+
+
+impl ReplaySource
+{
+	pub fn new( records: Vec< Record > ) -> Self
+	{
+		Self { records: records.into(), start: None }
+	}
+
+
+
+	/// An empty file is an empty recording, eg. when recording was stopped before the first event.
+	///
+	pub fn open( path: &Path ) -> Result< Self, Error >
+	{
+		let yaml = fs::read_to_string( path )?;
+
+		if yaml.trim().is_empty() { return Ok( Self::new( Vec::new() ) ) }
+
+
+		let records: Vec< Record > = serde_yaml::from_str( &yaml )?;
+
+		Ok( Self::new( records ) )
+	}
+}
+
+
+
+impl InputSource for ReplaySource
+{
+	// The clock starts on the first call, so time spent on setup is not counted.
+	//
+	fn next_event( &mut self ) -> Option< PadEvent >
+	{
+		let ( start, wall ) = *self.start.get_or_insert_with( || ( Instant::now(), SystemTime::now() ) );
+
+		if self.records.front()?.ms > millis( start.elapsed() ) { return None }
+
+
+		let rec = self.records.pop_front()?;
+
+		Some( PadEvent { pad: rec.pad, event: rec.event, time: wall + Duration::from_millis( rec.ms ) } )
+	}
+
+
+	fn pad_info( &self, _pad: usize ) -> Option< PadInfo >
+	{
+		None
+	}
+
+
+	fn done( &self ) -> bool
+	{
+		self.records.is_empty()
+	}
+}
+
+
+
+
+#[ cfg( test ) ]
+//
+mod tests
+{
+	use super::*;
+	use self::{ EventType::*, InputID::* };
+
+
+	// The events come out of the recording as they went in, with their times relative to the first one.
+	//
+	#[ test ]
+	//
+	fn round_trip()
+	{
+		let script =
+		[
+			(   0, ButtonPressed ( South           ) ),
+			(  15, AxisChanged   ( LeftStickX, 0.5 ) ),
+			(  30, ButtonReleased( South           ) ),
+			(  30, NoChange                          ),
+			( 250, Connected                         ),
+			( 260, ButtonPressed ( North           ) ),
+		];
+
+		// The recording starts after this, events from before the start would all be at 0 ms.
+		//
+		let start  = SystemTime::now() + Duration::from_secs( 1 );
+		let events = script.iter().map( |&( ms, event )| PadEvent { pad: 1, event, time: start + Duration::from_millis( ms ) } ).collect();
+		let path   = std::env::temp_dir().join( format!( "padawan-round-trip-{}.yml", std::process::id() ) );
+
+		let recorded = record( &mut ScriptSource::new( Vec::new(), events ), &path, &AtomicBool::new( true ) );
+		let replay   = ReplaySource::open( &path );
+
+		fs::remove_file( &path ).unwrap();
+		recorded.unwrap();
+
+
+		let records = replay.unwrap().records;
+		let first   = records[ 0 ].ms;
+
+		let played: Vec<_> = records.iter().map( |r| ( r.ms - first, r.pad, r.event ) ).collect();
+		let wanted: Vec<_> = script.iter().filter( |s| s.1 != NoChange ).map( |&( ms, event )| ( ms, 1, event ) ).collect();
+
+		assert_eq!( played, wanted );
+	}
+}
+
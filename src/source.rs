@@ -0,0 +1,192 @@
+// Input sources produce the gamepad events that drive the mapping engine. gilrs is the one for physical pads,
+// but events can just as well come from a recording or from a script in a test.
+//
+
+
+use std:: collections::VecDeque ;
+use std::        time::SystemTime;
+use std::         fmt            ;
+
+use super::*;
+
+use failure  ::Error;
+use gilrs::{ Gilrs, Event, Gamepad as GGamepad };
+
+
+
+/// An event from a specific gamepad.
+///
+#[ derive( Debug, Clone, Copy, PartialEq ) ]
+//
+pub struct PadEvent
+{
+	pub pad  : usize     ,
+	pub event: EventType ,
+	pub time : SystemTime,
+}
+
+
+
+/// Anything that produces gamepad events.
+///
+pub trait InputSource : fmt::Debug
+{
+	/// The next pending event, or None when there is nothing left to process right now.
+	/// The event loop sends a `NoChange` tick after every batch, and polls again later.
+	///
+	fn next_event( &mut self ) -> Option< PadEvent >;
+
+	/// What we know about a pad, so it can be matched against the `pads` section of the config.
+	///
+	fn pad_info( &self, pad: usize ) -> Option< PadInfo >;
+
+	/// The pads that are already connected when we start listening.
+	///
+	fn pads( &self ) -> Vec< PadInfo > { Vec::new() }
+
+	/// Whether this source will never produce events again. Physical devices never run out.
+	///
+	fn done( &self ) -> bool { false }
+}
+
+
+
+
+/// Events from physical gamepads, through gilrs.
+///
+pub struct GilrsSource
+{
+	gilrs: Gilrs
+}
+
+
+
+impl GilrsSource
+{
+	pub fn new() -> Result< Self, Error >
+	{
+		let gilrs = Gilrs::new().map_err( |e| PadError::Gilrs( format!( "{:?}", e ) ) )?;
+
+		Ok( Self { gilrs } )
+	}
+}
+
+
+
+impl InputSource for GilrsSource
+{
+	fn next_event( &mut self ) -> Option< PadEvent >
+	{
+		self.gilrs.next_event().map( |Event { id, event, time }|
+
+			PadEvent { pad: id, event: event.into(), time }
+		)
+	}
+
+
+	fn pad_info( &self, pad: usize ) -> Option< PadInfo >
+	{
+		self.gilrs.connected_gamepad( pad ).map( |gpad| pad_info( pad, gpad ) )
+	}
+
+
+	fn pads( &self ) -> Vec< PadInfo >
+	{
+		self.gilrs.gamepads().map( |( id, gpad )| pad_info( id, gpad ) ).collect()
+	}
+}
+
+
+
+// Gilrs doesn't implement Debug.
+//
+impl fmt::Debug for GilrsSource
+{
+	fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result
+	{
+		write!( f, "GilrsSource" )
+	}
+}
+
+
+
+fn pad_info( id: usize, gpad: &GGamepad ) -> PadInfo
+{
+	PadInfo
+	{
+		id  : id                     ,
+		name: gpad.name().to_string(),
+		uuid: gpad.uuid().to_string(),
+	}
+}
+
+
+
+
+/// Plays back a fixed list of events, meant for tests. A `NoChange` event in the script ends the current batch,
+/// so the engine ticks before the following events are delivered. When the script runs out, the source is done.
+///
+#[ derive( Debug, Clone, Default ) ]
+//
+pub struct ScriptSource
+{
+	pads  : Vec< PadInfo >       ,
+	events: VecDeque< PadEvent > ,
+}
+
+
+
+impl ScriptSource
+{
+	pub fn new( pads: Vec< PadInfo >, events: Vec< PadEvent > ) -> Self
+	{
+		Self { pads, events: events.into() }
+	}
+
+
+
+	/// Feed the rest of the script straight to one gamepad, without waiting between events. A `NoChange` in the
+	/// script is a poll of the event loop at it's time, so the time based actions run there.
+	///
+	pub fn play( &mut self, pad: &mut Gamepad, out: &mut dyn Output )
+	{
+		for PadEvent { event, time, .. } in self.events.drain( .. )
+		{
+			pad.process_event( event, time, out );
+		}
+	}
+}
+
+
+
+impl InputSource for ScriptSource
+{
+	fn next_event( &mut self ) -> Option< PadEvent >
+	{
+		let ev = self.events.pop_front()?;
+
+		match ev.event
+		{
+			EventType::NoChange => None    ,
+			_                   => Some( ev ),
+		}
+	}
+
+
+	fn pad_info( &self, pad: usize ) -> Option< PadInfo >
+	{
+		self.pads.iter().find( |p| p.id == pad ).cloned()
+	}
+
+
+	fn pads( &self ) -> Vec< PadInfo >
+	{
+		self.pads.clone()
+	}
+
+
+	fn done( &self ) -> bool
+	{
+		self.events.is_empty()
+	}
+}
@@ -0,0 +1,578 @@
+// The thumb sticks as a whole. Gilrs reports the two axes of a stick separately, but things like deadzones
+// only make sense on the position of the stick, so here we keep both axes together.
+//
+
+
+use std::  fmt::Debug     ;
+use std::  str::FromStr   ;
+use std:: time::SystemTime;
+
+use super::*;
+
+
+
+/// One of the thumb sticks. In a profile `LeftStick` and `RightStick` bind to the stick as a whole.
+///
+#[ derive( Debug, Clone, Copy, Eq, PartialEq, Hash ) ]
+//
+pub enum StickID
+{
+	LeftStick ,
+	RightStick,
+}
+
+
+
+impl StickID
+{
+	/// The X and Y axis of the stick.
+	///
+	pub fn axes( self ) -> ( InputID, InputID )
+	{
+		match self
+		{
+			StickID::LeftStick  => ( InputID::LeftStickX , InputID::LeftStickY  ),
+			StickID::RightStick => ( InputID::RightStickX, InputID::RightStickY ),
+		}
+	}
+
+
+	/// The stick an axis belongs to.
+	///
+	pub fn of( input: InputID ) -> Option< Self >
+	{
+		match input
+		{
+			InputID::LeftStickX  | InputID::LeftStickY  => Some( StickID::LeftStick  ),
+			InputID::RightStickX | InputID::RightStickY => Some( StickID::RightStick ),
+			_                                           => None                      ,
+		}
+	}
+}
+
+
+
+impl FromStr for StickID
+{
+	type Err = PadError;
+
+	fn from_str( name: &str ) -> Result< Self, PadError >
+	{
+		match name
+		{
+			"LeftStick"  => Ok ( StickID::LeftStick                        ),
+			"RightStick" => Ok ( StickID::RightStick                       ),
+			_            => Err( PadError::UnknownInput( name.to_string() ) ),
+		}
+	}
+}
+
+
+
+/// A circular deadzone. Below `inner` the stick counts as centered, from `outer` on as pushed all the way.
+/// In between the distance from the center is rescaled to go from 0 to 1, so there is no jump at the edge
+/// of the deadzone. The direction of the stick is kept.
+///
+#[ derive( Debug, Clone, Copy, PartialEq ) ]
+//
+pub struct Deadzone
+{
+	pub inner: f32,
+	pub outer: f32,
+}
+
+
+
+impl Deadzone
+{
+	pub fn new( inner: f32, outer: f32 ) -> Result< Self, PadError >
+	{
+		for &bound in &[ inner, outer ]
+		{
+			if !( bound >= 0.0 && bound <= 1.0 ) { return Err( PadError::BadDeadzone( bound ) ) }
+		}
+
+		if !( inner < outer )
+		{
+			return Err( PadError::BadStickDeadzone( inner, outer ) );
+		}
+
+		Ok( Self { inner, outer } )
+	}
+
+
+	pub fn apply( &self, x: f32, y: f32 ) -> ( f32, f32 )
+	{
+		let length = x.hypot( y );
+
+		if length <= self.inner { return ( 0.0, 0.0 ) }
+
+
+		let scaled = ( ( length - self.inner ) / ( self.outer - self.inner ) ).min( 1.0 );
+
+		( x / length * scaled, y / length * scaled )
+	}
+}
+
+
+
+/// Divides the directions of a stick in 4 or 8 sectors, for mapping a stick to keys. Sector 0 is right,
+/// and they go counter clockwise, so 2 is up. With 4 directions only the even sectors are used.
+/// With 8, `diagonal` is the width in degrees of the diagonal sectors, the others share what's left.
+/// With 4 there are no diagonals, so it can't be given.
+/// `hysteresis` is how far in degrees the stick can go beyond the edge of a sector before it leaves it,
+/// so the keys don't flicker when the stick is right on the edge.
+///
+#[ derive( Debug, Clone, Copy, PartialEq ) ]
+//
+pub struct Sectors
+{
+	directions: u8 ,
+	diagonal  : f32,
+	hysteresis: f32,
+}
+
+
+
+impl Sectors
+{
+	/// The width of the diagonals when none is given, the same as the other sectors.
+	///
+	pub const DIAGONAL: f32 = 45.0;
+
+
+	pub fn new( directions: u8, diagonal: Option< f32 >, hysteresis: f32 ) -> Result< Self, PadError >
+	{
+		let bad = |msg: String| Err( PadError::BadStickKeys( msg ) );
+
+		if directions != 4 && directions != 8
+		{
+			return bad( format!( "directions must be 4 or 8, got: {}", directions ) );
+		}
+
+		if directions == 4 && diagonal.is_some()
+		{
+			return bad( "diagonal_deg only works with 8 directions, there are no diagonals with 4".to_string() );
+		}
+
+
+		let diagonal = diagonal.unwrap_or( Self::DIAGONAL );
+
+		if !( diagonal > 0.0 && diagonal < 90.0 )
+		{
+			return bad( format!( "diagonal_deg must be between 0 and 90, got: {}", diagonal ) );
+		}
+
+
+		let sectors = Self { directions, diagonal, hysteresis };
+		let half    = ( 0..8 ).filter( |i| sectors.used( *i ) ).map( |i| sectors.width( i ) / 2.0 ).fold( 90.0, f32::min );
+
+		if !( hysteresis >= 0.0 && hysteresis < half )
+		{
+			return bad( format!( "hysteresis_deg must be at least 0 and below half the narrowest sector ({}), got: {}", half, hysteresis ) );
+		}
+
+		Ok( sectors )
+	}
+
+
+	fn used( &self, sector: usize ) -> bool
+	{
+		self.directions == 8 || sector % 2 == 0
+	}
+
+
+	fn width( &self, sector: usize ) -> f32
+	{
+		if      self.directions == 4 { 90.0                 }
+		else if sector % 2 == 1      { self.diagonal        }
+		else                         { 90.0 - self.diagonal }
+	}
+
+
+	// How far an angle is from the middle of a sector, in degrees.
+	//
+	fn distance( sector: usize, angle: f32 ) -> f32
+	{
+		let diff = ( angle - sector as f32 * 45.0 ).abs() % 360.0;
+
+		diff.min( 360.0 - diff )
+	}
+
+
+	/// The sector the stick is in. `current` is the sector it was in before, it has to move beyond the
+	/// hysteresis to leave it.
+	///
+	pub fn find( &self, x: f32, y: f32, current: Option< usize > ) -> usize
+	{
+		let angle = y.atan2( x ).to_degrees();
+
+		if let Some( current ) = current
+		{
+			if Self::distance( current, angle ) <= self.width( current ) / 2.0 + self.hysteresis { return current }
+		}
+
+
+		// The sectors cover the whole circle, the closest one is only a fallback for rounding errors.
+		//
+		let used = ( 0..8 ).filter( |i| self.used( *i ) );
+
+		if let Some( sector ) = used.clone().find( |i| Self::distance( *i, angle ) <= self.width( *i ) / 2.0 ) { return sector }
+
+		used.fold( 0, |best, i| if Self::distance( i, angle ) < Self::distance( best, angle ) { i } else { best } )
+	}
+}
+
+
+
+/// An action that needs the position of a whole stick rather than one axis.
+///
+pub trait StickAction : Debug
+{
+	fn run( &mut self, x: f32, y: f32, time: SystemTime, out: &mut dyn Output );
+
+	/// Stop whatever the action is doing, like `Action::release`.
+	//
+	fn release( &mut self, _out: &mut dyn Output ) {}
+}
+
+
+
+/// When to run a StickAction, like `Trigger` for the actions on a single input.
+///
+#[ derive( Debug ) ]
+//
+pub enum StickTrigger
+{
+	OnChange  ( Box< dyn StickAction > ),
+	OnNoChange( Box< dyn StickAction > ),
+}
+
+
+
+impl StickTrigger
+{
+	fn action_mut( &mut self ) -> &mut dyn StickAction
+	{
+		match self
+		{
+			StickTrigger::OnChange  ( act ) => act.as_mut(),
+			StickTrigger::OnNoChange( act ) => act.as_mut(),
+		}
+	}
+}
+
+
+
+/// The position of a stick that has something bound to it as a whole. The axis events of the stick go through
+/// here before they reach the actions bound to the axes.
+///
+#[ derive( Debug ) ]
+//
+pub struct Stick
+{
+	id      : StickID             ,
+	deadzone: Option< Deadzone >  ,
+	triggers: Vec< StickTrigger > ,
+	raw     : ( f32, f32 )        ,
+	state   : ( f32, f32 )        ,
+}
+
+
+
+impl Stick
+{
+	pub fn new( id: StickID ) -> Self
+	{
+		Self { id, deadzone: None, triggers: Vec::new(), raw: ( 0.0, 0.0 ), state: ( 0.0, 0.0 ) }
+	}
+
+
+	/// Where the stick is, before the deadzone.
+	///
+	pub fn raw( &self ) -> ( f32, f32 )
+	{
+		self.raw
+	}
+
+
+
+	pub fn set_deadzone( &mut self, deadzone: Deadzone )
+	{
+		self.deadzone = Some( deadzone );
+	}
+
+
+	pub fn add_trigger( &mut self, trigger: StickTrigger )
+	{
+		self.triggers.push( trigger );
+	}
+
+
+	/// Update one axis, run the OnChange triggers and return the axis events to send on. Moving one axis
+	/// can change the value of the other one after the deadzone, eg. when the stick leaves the deadzone,
+	/// so this can return an event for both.
+	///
+	pub fn process_axis( &mut self, input: InputID, value: f32, time: SystemTime, out: &mut dyn Output ) -> Vec< EventType >
+	{
+		let ( x_id, y_id ) = self.id.axes();
+
+		if input == x_id { self.raw.0 = value }
+		else             { self.raw.1 = value }
+
+
+		let deadzone = match self.deadzone
+		{
+			Some( deadzone ) => deadzone,
+
+			None =>
+			{
+				let old = std::mem::replace( &mut self.state, self.raw );
+
+				if old != self.state { self.run( true, time, out ) }
+
+				return vec![ EventType::AxisChanged( input, value ) ];
+			}
+		};
+
+
+		let ( x, y ) = deadzone.apply( self.raw.0, self.raw.1 );
+		let mut events = Vec::with_capacity( 2 );
+
+		if x != self.state.0 { events.push( EventType::AxisChanged( x_id, x ) ) }
+		if y != self.state.1 { events.push( EventType::AxisChanged( y_id, y ) ) }
+
+		self.state = ( x, y );
+
+		if !events.is_empty() { self.run( true, time, out ) }
+
+		events
+	}
+
+
+	/// Run the OnNoChange triggers, on every poll of the event loop.
+	///
+	pub fn tick( &mut self, time: SystemTime, out: &mut dyn Output )
+	{
+		self.run( false, time, out );
+	}
+
+
+	// Run the OnChange triggers if `change`, the OnNoChange ones otherwise.
+	//
+	fn run( &mut self, change: bool, time: SystemTime, out: &mut dyn Output )
+	{
+		let ( x, y ) = self.state;
+
+		// The keys pressed by stick actions belong to the X axis, so they get released with it.
+		//
+		out.source( self.id.axes().0 );
+
+		for trigger in &mut self.triggers
+		{
+			match trigger
+			{
+				StickTrigger::OnChange  ( act ) if  change => act.run( x, y, time, out ),
+				StickTrigger::OnNoChange( act ) if !change => act.run( x, y, time, out ),
+				_                                          => ()                        ,
+			}
+		}
+	}
+
+
+	/// Stop all actions and forget the position, eg. when the gamepad disconnects.
+	///
+	pub fn release_all( &mut self, out: &mut dyn Output )
+	{
+		out.source( self.id.axes().0 );
+
+		for trigger in &mut self.triggers { trigger.action_mut().release( out ) }
+
+		self.raw   = ( 0.0, 0.0 );
+		self.state = ( 0.0, 0.0 );
+	}
+}
+
+
+
+#[ cfg( test ) ]
+//
+mod tests
+{
+	use super::*;
+
+
+	// The sector for a full deflection at an angle in degrees.
+	//
+	fn find( sectors: &Sectors, deg: f32, current: Option< usize > ) -> usize
+	{
+		let rad = deg.to_radians();
+
+		sectors.find( rad.cos(), rad.sin(), current )
+	}
+
+
+
+	#[ test ]
+	//
+	fn eight_directions()
+	{
+		let sectors = Sectors::new( 8, None, 0.0 ).unwrap();
+
+		assert_eq!( find( &sectors,   0.0, None ), 0 );
+		assert_eq!( find( &sectors,  22.0, None ), 0 );
+		assert_eq!( find( &sectors,  23.0, None ), 1 );
+		assert_eq!( find( &sectors,  67.0, None ), 1 );
+		assert_eq!( find( &sectors,  68.0, None ), 2 );
+		assert_eq!( find( &sectors, 180.0, None ), 4 );
+		assert_eq!( find( &sectors, -90.0, None ), 6 );
+		assert_eq!( find( &sectors, -23.0, None ), 7 );
+		assert_eq!( find( &sectors, -22.0, None ), 0 );
+	}
+
+
+
+	#[ test ]
+	//
+	fn diagonal_width()
+	{
+		let sectors = Sectors::new( 8, Some( 30.0 ), 0.0 ).unwrap();
+
+		assert_eq!( find( &sectors,  29.0, None ), 0 );
+		assert_eq!( find( &sectors,  31.0, None ), 1 );
+		assert_eq!( find( &sectors,  59.0, None ), 1 );
+		assert_eq!( find( &sectors,  61.0, None ), 2 );
+		assert_eq!( find( &sectors, 149.0, None ), 3 );
+		assert_eq!( find( &sectors, 151.0, None ), 4 );
+	}
+
+
+
+	#[ test ]
+	//
+	fn hysteresis()
+	{
+		let sectors = Sectors::new( 8, None, 5.0 ).unwrap();
+
+		// Without a current sector the edges are where they are without hysteresis.
+		//
+		assert_eq!( find( &sectors, 23.0, None ), 1 );
+
+		assert_eq!( find( &sectors, 27.0, Some( 0 ) ), 0 );
+		assert_eq!( find( &sectors, 28.0, Some( 0 ) ), 1 );
+		assert_eq!( find( &sectors, 18.0, Some( 1 ) ), 1 );
+		assert_eq!( find( &sectors, 17.0, Some( 1 ) ), 0 );
+
+		// Across the -180/180 boundary.
+		//
+		assert_eq!( find( &sectors, -155.0, Some( 4 ) ), 4 );
+		assert_eq!( find( &sectors, -152.0, Some( 4 ) ), 5 );
+	}
+
+
+
+	#[ test ]
+	//
+	fn four_directions()
+	{
+		let sectors = Sectors::new( 4, None, 0.0 ).unwrap();
+
+		assert_eq!( find( &sectors,   44.0, None ), 0 );
+		assert_eq!( find( &sectors,   46.0, None ), 2 );
+		assert_eq!( find( &sectors,  134.0, None ), 2 );
+		assert_eq!( find( &sectors,  136.0, None ), 4 );
+		assert_eq!( find( &sectors, -136.0, None ), 4 );
+		assert_eq!( find( &sectors, -134.0, None ), 6 );
+		assert_eq!( find( &sectors,  -46.0, None ), 6 );
+
+		let sectors = Sectors::new( 4, None, 10.0 ).unwrap();
+
+		assert_eq!( find( &sectors, 54.0, Some( 0 ) ), 0 );
+		assert_eq!( find( &sectors, 56.0, Some( 0 ) ), 2 );
+	}
+
+
+
+	// Right on the edges, where rounding decides, 4 directions must never give a diagonal.
+	//
+	#[ test ]
+	//
+	fn four_directions_never_diagonal()
+	{
+		let sectors = Sectors::new( 4, None, 0.0 ).unwrap();
+
+		for edge in &[ 45.0, 135.0, -135.0, -45.0 ]
+		{
+			for step in -100..100
+			{
+				let deg = *edge + step as f32 * 1e-5;
+
+				assert_eq!( find( &sectors, deg, None ) % 2, 0, "odd sector at {}", deg );
+			}
+		}
+
+
+		// Exactly between two sectors.
+		//
+		assert_eq!( sectors.find( 1.0, 1.0, None ) % 2, 0 );
+		assert_eq!( sectors.find( -1.0, -1.0, None ) % 2, 0 );
+	}
+
+
+
+	#[ test ]
+	//
+	fn invalid()
+	{
+		assert!( Sectors::new( 6, None        , 0.0  ).is_err() );
+		assert!( Sectors::new( 4, Some( 45.0 ), 0.0  ).is_err() );
+		assert!( Sectors::new( 8, Some( 90.0 ), 0.0  ).is_err() );
+		assert!( Sectors::new( 8, None        , 22.5 ).is_err() );
+		assert!( Sectors::new( 8, None        , -1.0 ).is_err() );
+	}
+
+
+
+	fn close( a: ( f32, f32 ), b: ( f32, f32 ) ) -> bool
+	{
+		( a.0 - b.0 ).abs() < 1e-6 && ( a.1 - b.1 ).abs() < 1e-6
+	}
+
+
+
+	// Nothing up to inner, 0 to 1 from inner to outer, and 1 beyond. The direction doesn't change.
+	//
+	#[ test ]
+	//
+	fn deadzone()
+	{
+		let dz = Deadzone::new( 0.2, 0.8 ).unwrap();
+
+		assert_eq!( dz.apply( 0.1, 0.1 ), ( 0.0, 0.0 ) );
+		assert_eq!( dz.apply( 0.2, 0.0 ), ( 0.0, 0.0 ) );
+
+		assert!( close( dz.apply(  0.5,  0.0 ), (  0.5,  0.0 ) ) );
+		assert!( close( dz.apply(  0.8,  0.0 ), (  1.0,  0.0 ) ) );
+		assert!( close( dz.apply(  1.0,  0.0 ), (  1.0,  0.0 ) ) );
+		assert!( close( dz.apply(  0.0, -0.5 ), (  0.0, -0.5 ) ) );
+
+		// 0.3, 0.4 is 0.5 from the center, halfway between inner and outer.
+		//
+		assert!( close( dz.apply(  0.3,  0.4 ), (  0.3,  0.4 ) ) );
+		assert!( close( dz.apply( -0.6,  0.8 ), ( -0.6,  0.8 ) ) );
+	}
+
+
+
+	#[ test ]
+	//
+	fn deadzone_bounds()
+	{
+		assert!( Deadzone::new(  0.0, 1.0 ).is_ok () );
+		assert!( Deadzone::new(  0.5, 0.5 ).is_err() );
+		assert!( Deadzone::new(  0.6, 0.4 ).is_err() );
+		assert!( Deadzone::new( -0.1, 0.9 ).is_err() );
+		assert!( Deadzone::new(  0.1, 1.5 ).is_err() );
+	}
+}
+
@@ -0,0 +1,355 @@
+// Output through a virtual uinput device. This goes through the kernel rather than the display server,
+// so it works on Wayland, on a TTY and in games that ignore XTest.
+//
+// Note that uinput sends keycodes, not characters. Characters (Key::Layout) are translated to the key
+// that produces them without shift on the keyboard layout from the config. The OS keyboard layout then decides
+// which character the application receives, so the two have to match. Key::Raw is sent as is, as a linux keycode.
+//
+
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::*;
+
+use failure  ::Error;
+use ::enigo::{ Key, MouseButton };
+
+use evdev::{ AttributeSet, InputEvent, EventType as EvType, Key as EvKey, RelativeAxisType };
+use evdev::uinput::{ VirtualDevice, VirtualDeviceBuilder };
+
+
+
+/// Injects events through a virtual keyboard/mouse created with /dev/uinput.
+/// This needs write access to /dev/uinput.
+///
+pub struct UinputOutput
+{
+	dev   : VirtualDevice,
+	layout: Layout       ,
+}
+
+
+
+impl UinputOutput
+{
+	pub fn new( layout: Layout ) -> Result< Self, Error >
+	{
+		let mut keys = AttributeSet::< EvKey >::new();
+
+		// Everything up to KEY_MICMUTE is a keyboard key. Registering all of them allows Key::Raw to work.
+		//
+		for code in 1..=248 { keys.insert( EvKey::new( code ) ) }
+
+		keys.insert( EvKey::BTN_LEFT   );
+		keys.insert( EvKey::BTN_RIGHT  );
+		keys.insert( EvKey::BTN_MIDDLE );
+
+
+		let mut axes = AttributeSet::< RelativeAxisType >::new();
+
+		axes.insert( RelativeAxisType::REL_X      );
+		axes.insert( RelativeAxisType::REL_Y      );
+		axes.insert( RelativeAxisType::REL_WHEEL  );
+		axes.insert( RelativeAxisType::REL_HWHEEL );
+
+
+		let dev = VirtualDeviceBuilder::new()?
+
+			.name              ( "padawan"  )
+			.with_keys         ( &keys      )?
+			.with_relative_axes( &axes      )?
+			.build()?
+		;
+
+		Ok( Self { dev, layout } )
+	}
+
+
+
+	fn emit( &mut self, events: &[ InputEvent ] )
+	{
+		if let Err( e ) = self.dev.emit( events )
+		{
+			eprintln!( "Failed to write to uinput device: {}", e );
+		}
+	}
+
+
+
+	fn key( &mut self, key: Clickable, value: i32 )
+	{
+		let code = match key
+		{
+			Clickable::Keyboard( key ) => key_code( key, self.layout ),
+
+			Clickable::Mouse( MouseButton::Left   ) => Some( EvKey::BTN_LEFT   ),
+			Clickable::Mouse( MouseButton::Middle ) => Some( EvKey::BTN_MIDDLE ),
+			Clickable::Mouse( MouseButton::Right  ) => Some( EvKey::BTN_RIGHT  ),
+
+			// The wheel has no up state, so we only scroll on press.
+			//
+			Clickable::Mouse( wheel ) =>
+			{
+				if value == 1 { self.wheel( wheel ) }
+				return
+			}
+		};
+
+
+		match code
+		{
+			Some( code ) => self.emit( &[ InputEvent::new( EvType::KEY, code.code(), value ) ] ),
+			None         => eprintln!( "The uinput output has no keycode for: {:?}", key )     ,
+		}
+	}
+
+
+
+	fn wheel( &mut self, button: MouseButton )
+	{
+		let ( axis, value ) = match button
+		{
+			MouseButton::ScrollUp    => ( RelativeAxisType::REL_WHEEL ,  1 ),
+			MouseButton::ScrollDown  => ( RelativeAxisType::REL_WHEEL , -1 ),
+			MouseButton::ScrollLeft  => ( RelativeAxisType::REL_HWHEEL, -1 ),
+			MouseButton::ScrollRight => ( RelativeAxisType::REL_HWHEEL,  1 ),
+			_                        => return                              ,
+		};
+
+		self.emit( &[ InputEvent::new( EvType::RELATIVE, axis.0, value ) ] );
+	}
+}
+
+
+
+impl Output for UinputOutput
+{
+	fn press  ( &mut self, key: Clickable ) { self.key( key, 1 ) }
+	fn release( &mut self, key: Clickable ) { self.key( key, 0 ) }
+
+
+	fn move_mouse( &mut self, x: i32, y: i32 )
+	{
+		let mut events = Vec::with_capacity( 2 );
+
+		if x != 0 { events.push( InputEvent::new( EvType::RELATIVE, RelativeAxisType::REL_X.0, x ) ) }
+		if y != 0 { events.push( InputEvent::new( EvType::RELATIVE, RelativeAxisType::REL_Y.0, y ) ) }
+
+		if !events.is_empty() { self.emit( &events ) }
+	}
+
+
+	fn scroll( &mut self, x: i32, y: i32 )
+	{
+		let mut events = Vec::with_capacity( 2 );
+
+		if x != 0 { events.push( InputEvent::new( EvType::RELATIVE, RelativeAxisType::REL_HWHEEL.0, x ) ) }
+		if y != 0 { events.push( InputEvent::new( EvType::RELATIVE, RelativeAxisType::REL_WHEEL .0, y ) ) }
+
+		if !events.is_empty() { self.emit( &events ) }
+	}
+}
+
+
+
+impl fmt::Debug for UinputOutput
+{
+	fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result
+	{
+		write!( f, "UinputOutput" )
+	}
+}
+
+
+
+/// Make sure the uinput output can type every character in an action with the given keyboard layout.
+//
+pub fn check_keys( action: &ActionCfg, layout: Layout ) -> Result< (), PadError >
+{
+	for keys in action.keys()
+	{
+		for key in Chord::try_from( keys )?.0
+		{
+			if let Clickable::Keyboard( Key::Layout( c ) ) = key
+			{
+				if char_code( c, layout ).is_none() { return Err( PadError::CantType( c, layout ) ) }
+			}
+		}
+	}
+
+	Ok(())
+}
+
+
+
+/// Translate an enigo key to a linux keycode.
+//
+fn key_code( key: Key, layout: Layout ) -> Option< EvKey >
+{
+	let code = match key
+	{
+		Key::Return     => EvKey::KEY_ENTER     ,
+		Key::Tab        => EvKey::KEY_TAB       ,
+		Key::Space      => EvKey::KEY_SPACE     ,
+		Key::Backspace  => EvKey::KEY_BACKSPACE ,
+		Key::Escape     => EvKey::KEY_ESC       ,
+		Key::Super      => EvKey::KEY_LEFTMETA  ,
+		Key::Command    => EvKey::KEY_LEFTMETA  ,
+		Key::Windows    => EvKey::KEY_LEFTMETA  ,
+		Key::Shift      => EvKey::KEY_LEFTSHIFT ,
+		Key::CapsLock   => EvKey::KEY_CAPSLOCK  ,
+		Key::Alt        => EvKey::KEY_LEFTALT   ,
+		Key::Option     => EvKey::KEY_LEFTALT   ,
+		Key::Control    => EvKey::KEY_LEFTCTRL  ,
+		Key::Home       => EvKey::KEY_HOME      ,
+		Key::PageUp     => EvKey::KEY_PAGEUP    ,
+		Key::PageDown   => EvKey::KEY_PAGEDOWN  ,
+		Key::LeftArrow  => EvKey::KEY_LEFT      ,
+		Key::RightArrow => EvKey::KEY_RIGHT     ,
+		Key::DownArrow  => EvKey::KEY_DOWN      ,
+		Key::UpArrow    => EvKey::KEY_UP        ,
+		Key::F1         => EvKey::KEY_F1        ,
+		Key::F2         => EvKey::KEY_F2        ,
+		Key::F3         => EvKey::KEY_F3        ,
+		Key::F4         => EvKey::KEY_F4        ,
+		Key::F5         => EvKey::KEY_F5        ,
+		Key::F6         => EvKey::KEY_F6        ,
+		Key::F7         => EvKey::KEY_F7        ,
+		Key::F8         => EvKey::KEY_F8        ,
+		Key::F9         => EvKey::KEY_F9        ,
+		Key::F10        => EvKey::KEY_F10       ,
+		Key::F11        => EvKey::KEY_F11       ,
+		Key::F12        => EvKey::KEY_F12       ,
+
+		Key::Raw   ( raw ) => EvKey::new( raw )    ,
+		Key::Layout( c   ) => return char_code( c, layout ),
+	};
+
+	Some( code )
+}
+
+
+
+/// The key that produces a character without shift. Capitals and other characters that need shift have none,
+/// they are written as a chord, eg. `Shift+a`.
+//
+fn char_code( c: char, layout: Layout ) -> Option< EvKey >
+{
+	match layout
+	{
+		Layout::Qwerty => qwerty( c ),
+		Layout::Azerty => azerty( c ),
+	}
+}
+
+
+
+fn qwerty( c: char ) -> Option< EvKey >
+{
+	let code = match c
+	{
+		'a' => EvKey::KEY_A, 'b' => EvKey::KEY_B, 'c' => EvKey::KEY_C, 'd' => EvKey::KEY_D,
+		'e' => EvKey::KEY_E, 'f' => EvKey::KEY_F, 'g' => EvKey::KEY_G, 'h' => EvKey::KEY_H,
+		'i' => EvKey::KEY_I, 'j' => EvKey::KEY_J, 'k' => EvKey::KEY_K, 'l' => EvKey::KEY_L,
+		'm' => EvKey::KEY_M, 'n' => EvKey::KEY_N, 'o' => EvKey::KEY_O, 'p' => EvKey::KEY_P,
+		'q' => EvKey::KEY_Q, 'r' => EvKey::KEY_R, 's' => EvKey::KEY_S, 't' => EvKey::KEY_T,
+		'u' => EvKey::KEY_U, 'v' => EvKey::KEY_V, 'w' => EvKey::KEY_W, 'x' => EvKey::KEY_X,
+		'y' => EvKey::KEY_Y, 'z' => EvKey::KEY_Z,
+
+		'1' => EvKey::KEY_1, '2' => EvKey::KEY_2, '3' => EvKey::KEY_3, '4' => EvKey::KEY_4,
+		'5' => EvKey::KEY_5, '6' => EvKey::KEY_6, '7' => EvKey::KEY_7, '8' => EvKey::KEY_8,
+		'9' => EvKey::KEY_9, '0' => EvKey::KEY_0,
+
+		' '  => EvKey::KEY_SPACE     ,
+		'-'  => EvKey::KEY_MINUS     ,
+		'='  => EvKey::KEY_EQUAL     ,
+		'['  => EvKey::KEY_LEFTBRACE ,
+		']'  => EvKey::KEY_RIGHTBRACE,
+		';'  => EvKey::KEY_SEMICOLON ,
+		'\'' => EvKey::KEY_APOSTROPHE,
+		'`'  => EvKey::KEY_GRAVE     ,
+		'\\' => EvKey::KEY_BACKSLASH ,
+		','  => EvKey::KEY_COMMA     ,
+		'.'  => EvKey::KEY_DOT       ,
+		'/'  => EvKey::KEY_SLASH     ,
+
+		_    => return None          ,
+	};
+
+	Some( code )
+}
+
+
+
+fn azerty( c: char ) -> Option< EvKey >
+{
+	let code = match c
+	{
+		'a' => EvKey::KEY_Q, 'q' => EvKey::KEY_A, 'z' => EvKey::KEY_W, 'w' => EvKey::KEY_Z,
+		'm' => EvKey::KEY_SEMICOLON,
+
+		'²' => EvKey::KEY_GRAVE, '&' => EvKey::KEY_1, 'é' => EvKey::KEY_2, '"' => EvKey::KEY_3,
+		'\'' => EvKey::KEY_4    , '(' => EvKey::KEY_5, '-' => EvKey::KEY_6, 'è' => EvKey::KEY_7,
+		'_' => EvKey::KEY_8    , 'ç' => EvKey::KEY_9, 'à' => EvKey::KEY_0,
+
+		')' => EvKey::KEY_MINUS     ,
+		'=' => EvKey::KEY_EQUAL     ,
+		'^' => EvKey::KEY_LEFTBRACE ,
+		'$' => EvKey::KEY_RIGHTBRACE,
+		'ù' => EvKey::KEY_APOSTROPHE,
+		'*' => EvKey::KEY_BACKSLASH ,
+		'<' => EvKey::KEY_102ND     ,
+		',' => EvKey::KEY_M         ,
+		';' => EvKey::KEY_COMMA     ,
+		':' => EvKey::KEY_DOT       ,
+		'!' => EvKey::KEY_SLASH     ,
+
+		// The other letters and space are where they are on qwerty.
+		//
+		c if c.is_ascii_lowercase() || c == ' ' => return qwerty( c ),
+
+		_ => return None,
+	};
+
+	Some( code )
+}
+
+
+
+
+#[ cfg( test ) ]
+//
+mod tests
+{
+	use super::*;
+
+
+	// The character that can't be typed, if any.
+	//
+	fn check( key: &str, layout: Layout ) -> Result< (), char >
+	{
+		check_keys( &ActionCfg::Button( key.to_string() ), layout ).map_err( |e| match e
+		{
+			PadError::CantType( c, _ ) => c                ,
+			e                          => panic!( "{}", e ),
+		})
+	}
+
+
+
+	#[ test ]
+	//
+	fn shifted_characters()
+	{
+		assert_eq!( check( "a"      , Layout::Qwerty ), Ok ( ()  ) );
+		assert_eq!( check( "Shift+a", Layout::Qwerty ), Ok ( ()  ) );
+		assert_eq!( check( "1"      , Layout::Qwerty ), Ok ( ()  ) );
+		assert_eq!( check( "&"      , Layout::Azerty ), Ok ( ()  ) );
+
+		assert_eq!( check( "A"      , Layout::Qwerty ), Err( 'A' ) );
+		assert_eq!( check( "!"      , Layout::Qwerty ), Err( '!' ) );
+		assert_eq!( check( "A"      , Layout::Azerty ), Err( 'A' ) );
+		assert_eq!( check( "1"      , Layout::Azerty ), Err( '1' ) );
+	}
+}